@@ -1,10 +1,13 @@
-use crate::midi_handler::{send_cc_change, send_note_on, send_note_off};
+
+use crate::midi_handler::{send_cc_change, send_note_on, send_note_off, send_parameter_value, send_parameter_increment, send_pitch_bend, ParameterNumber};
 use std::sync::mpsc::{Sender, Receiver, channel};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::collections::HashMap;
 use std::f32::consts::PI;
-use crate::xinput_handler::{ControllerState, ButtonState};
-use std::time::Instant;
+use crate::xinput_handler::{ControllerState, ButtonState, StickState, TriggerState, Rumble, request_rumble};
+use crate::settings::{Settings, MidiChannels};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum DeckType {
@@ -15,32 +18,479 @@ enum DeckType {
 
 impl DeckType {
     fn midi_channel(&self) -> u8 {
+        let midi_channels = MIDI_CHANNELS.lock().unwrap();
+        match self {
+            DeckType::Left | DeckType::Right => midi_channels.decks[self.deck_index()],
+            DeckType::Common => midi_channels.common,
+        }
+    }
+
+    // 現在操作しているMixxxのデッキ（0から3、デッキ3/4を操作している側は2つ後ろ）
+    fn deck_index(&self) -> usize {
+        match self {
+            DeckType::Left | DeckType::Right => {
+                let offset = if DECK_FOCUS.lock().unwrap()[self.stick_index()] { 2 } else { 0 };
+                self.stick_index() + offset
+            },
+            DeckType::Common => unreachable!("DeckType common doesn't have a deck number"),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            DeckType::Left => "Left",
+            DeckType::Right => "Right",
+            DeckType::Common => "Common",
+        }
+    }
+
+    fn stick_index(&self) -> usize {
         match self {
             DeckType::Left => 0,
             DeckType::Right => 1,
-            DeckType::Common => 15,
+            DeckType::Common => unreachable!("DeckType common doesn't have a stick"),
         }
     }
 }
 
 #[derive(Debug, Clone, Copy)]
+#[allow(dead_code)] // 既定のマッピングでは使っていない挙動も、マッピングの表で選べるよう残しておく
 // スティックからCC値の変換方法
 enum Behavior {
     CCAbsolute,    // 通常の角度→CC値の変換
     CCRelative,    // 角度の差分→CC値の変換
     Note,          // ノートオン/オフの送信
+    Axis {         // スティックの片方の軸の倒し具合→CC値の変換（中央が0.5）
+        axis: StickAxis,
+        spring_return: Option<f32>,  // スティックを離した時に送信する値（Noneなら最後の値を保持）
+    },
+    Flick {        // スティックを片方の軸の方向に弾くたびに1ステップの相対値を送信（ライブラリの左右移動など）
+        axis: StickAxis,
+    },
+    Detent {       // 角度をcount段階に区切り、段階が変わった時だけ送信
+        count: u8,
+        base_note: Option<u8>,  // 指定した場合はCCの代わりに base_note + 段階 のノートを送信
+    },
+    XYPad {        // スティックのX軸とY軸をそれぞれ別のCCとして送信（中央が0.5）
+        x_cc: u8,
+        y_cc: u8,
+        latch: bool,  // trueならスティックを離しても最後の位置を保持、falseなら中央に戻す
+    },
+    Toggle {       // 押すたびにオンとオフを切り替え、それぞれのベロシティでノートオンを送信
+        on_velocity: u8,
+        off_velocity: u8,
+    },
+    RadialMenu {   // 押している間スティックを倒した方向の機能を選び、離した時に確定（12時から時計回りに最大8個）
+        items: &'static [u8],  // 選択肢のCC番号（同じデッキのCCマッピングに存在するもの）
+    },
+    Cycle {        // 押すたびに選択肢の次（directionが負なら前）の機能に切り替える
+        items: &'static [u8],  // 選択肢のCC番号（同じデッキのCCマッピングに存在するもの）
+        direction: i8,
+        rumble: bool,          // 切り替えた機能の番号の回数だけ振動する
+    },
+    Macro {        // 押した時/離した時にメッセージの列を順に送信
+        on_press: &'static [MacroStep],
+        on_release: &'static [MacroStep],
+    },
+    Step {         // 押した時にstepsステップの相対値をcc_numberに送信
+        steps: i32,
+    },
+    Ramp {         // 押している間、値を一定の速さで増減させる（cc_numberがNoneならスティックに割り当てられている機能が対象）
+        rate: f32,          // 1秒あたりの変化量（0.0から1.0の範囲に対して、負なら減少）
+        acceleration: f32,  // 押し続けた1秒ごとに速さに加える倍率（0.0なら一定）
+    },
+    DeckSelect,    // 押すたびにその側で操作するデッキを切り替える（左はデッキ1/3、右はデッキ2/4）
+    Crossfader {   // LTで左、RTで右に引くクロスフェーダー（両方離すと中央、button_getterは使わない）
+        curve: CrossfaderCurve,
+        cut: bool,  // トランスフォーマー（トリガーを引いた側に瞬時に切り替え、離すと中央に戻す）
+    },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)] // 既定のクロスフェーダーはSmoothなので、他の曲線はマッピングで選んだ時に使う
+// トリガーの引き具合からクロスフェーダーの動きへの変換
+enum CrossfaderCurve {
+    Smooth,    // 引き切って端に届く
+    SharpCut,  // 半分引けば端に届く
+    Scratch,   // 少し引くだけで端に届く（スクラッチ用）
+}
+
+impl CrossfaderCurve {
+    // トリガーの引き具合（0.0から1.0）を中央からの移動量（0.0から1.0）に変換する
+    fn apply(&self, value: f32, cut: bool) -> f32 {
+        const CUT_THRESHOLD: f32 = 0.1; // トランスフォーマーで切り替わる引き具合
+        if cut {
+            return if value >= CUT_THRESHOLD { 1.0 } else { 0.0 };
+        }
+        let full_travel = match self {
+            CrossfaderCurve::Smooth => 1.0,
+            CrossfaderCurve::SharpCut => 0.5,
+            CrossfaderCurve::Scratch => 0.1,
+        };
+        (value / full_travel).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)] // Axis/Flickのマッピングで選ぶ
+enum StickAxis {
+    X,  // 右に倒すと増加
+    Y,  // 上に倒すと増加
+}
+
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)] // Macroのマッピングで組み合わせる
+// マクロで送信するメッセージ
+enum MacroStep {
+    NoteOn { deck: DeckType, note: u8, velocity: u8 },
+    NoteOff { deck: DeckType, note: u8 },
+    Note { deck: DeckType, note: u8 },  // ノートオンの直後にノートオフ
+    ControlChange { deck: DeckType, cc: u8, value: u8 },
+    Wait(u64),                          // 指定したミリ秒だけ待つ
+}
+
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)] // マッピングで選択できるよう、既定のマッピングで使っていない種類も残しておく
+// スティックの値を送信するメッセージの種類
+enum Output {
+    ControlChange,               // cc_numberのCCとして送信（7bit）
+    Parameter(ParameterNumber),  // NRPN/RPNとして送信（14bit、相対値はIncrement/Decrement）
+    PitchBend,                   // デッキのチャンネルのピッチベンドとして送信（14bit、相対値は中央からのオフセット）
+}
+
+impl Output {
+    // 絶対値を送信する時の最大値
+    fn resolution(&self) -> f32 {
+        match self {
+            Output::ControlChange => 127.0,
+            Output::Parameter(_) | Output::PitchBend => 16383.0,
+        }
+    }
+
+    // 0.0から1.0の値を送信する値に変換する（0.5が中央の64/8192になるよう四捨五入）
+    fn quantize(&self, value: f32) -> u16 {
+        (value.clamp(0.0, 1.0) * self.resolution()).round() as u16
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)] // XInputDJ.wheelTurn以外のスクリプトに合わせて選べるよう、他の方式も残しておく
+// 相対値をCC値で表現する方式（CCRelativeのみ）
+enum RelativeEncoding {
+    TwosComplement,  // 負の値は2の補数（127 = -1、XInputDJ.wheelTurn用）
+    BinaryOffset,    // 64を0として加減（63 = -1、65 = +1）
+    SignMagnitude,   // bit6が符号（65 = -1、1 = +1）
+    Mackie,          // Mackie ControlのV-Pot形式（SignMagnitudeと同じだが1メッセージ最大15ステップ）
+}
+
+impl RelativeEncoding {
+    // ステップ数をCC値に変換する
+    fn encode(&self, steps: i32) -> u8 {
+        match self {
+            RelativeEncoding::TwosComplement => (steps.clamp(-64, 63) & 0x7F) as u8,
+            RelativeEncoding::BinaryOffset => (64 + steps.clamp(-64, 63)) as u8,
+            RelativeEncoding::SignMagnitude => {
+                let magnitude = steps.unsigned_abs().min(63) as u8;
+                if steps < 0 { 0x40 | magnitude } else { magnitude }
+            },
+            RelativeEncoding::Mackie => {
+                let magnitude = steps.unsigned_abs().min(15) as u8;
+                if steps < 0 { 0x40 | magnitude } else { magnitude }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+// スティックを回す速さ（rad/s）に応じてステップ数にかける倍率
+struct Acceleration {
+    min_speed: f32,  // この速さ以下ではmin_gain倍
+    max_speed: f32,  // この速さ以上ではmax_gain倍
+    min_gain: f32,   // ゆっくり回した時の倍率（1.0未満で微調整しやすくなる）
+    max_gain: f32,   // 速く回した時の倍率
+    exponent: f32,   // 倍率の変化カーブ（1.0で直線、大きいほど速く回した時だけ加速する）
+}
+
+impl Acceleration {
+    fn gain(&self, speed: f32) -> f32 {
+        // 範囲の幅が0の場合はmax_speedを境に切り替える
+        if self.max_speed <= self.min_speed {
+            return if speed >= self.max_speed { self.max_gain } else { self.min_gain };
+        }
+        let t = ((speed - self.min_speed) / (self.max_speed - self.min_speed)).clamp(0.0, 1.0);
+        self.min_gain + (self.max_gain - self.min_gain) * t.powf(self.exponent)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+// スティックを素早く回して離した後も、減速しながら回り続ける慣性（CCRelativeのみ）
+struct Inertia {
+    min_speed: f32,  // 離した時にこの速さ（ステップ/秒）以上で回っていたら慣性で回し続ける
+    friction: f32,   // 減速の強さ（1秒あたり速さが e^-friction 倍になる）
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+// ボタンを押し続けた時のキーリピート（NoteとStepのみ）
+struct AutoRepeat {
+    delay_ms: u64,         // 押してから繰り返し始めるまでの時間
+    interval_ms: u64,      // 最初の繰り返しの間隔
+    min_interval_ms: u64,  // 繰り返しの間隔の最小値
+    speedup: f32,          // 繰り返すたびに間隔にかける倍率（1.0で一定、小さいほど早く加速する）
+}
+
+#[derive(Debug, Clone, Copy)]
+// マッピングごとのキーリピートの状態
+struct RepeatState {
+    next: Instant,
+    interval: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+// マッピングごとの値の増減の状態（Ramp用）
+struct RampState {
+    pressed_at: Instant,
+    last_update: Instant,
+}
+
+#[derive(Debug, Default)]
+// マッピングごとのマクロの再生状態
+struct MacroRun {
+    generation: u64,          // 再生し直すと増え、古い再生は中断される
+    sounding: Vec<(u8, u8)>,  // ノートオンを送信してまだノートオフを送信していないノート（チャンネル、ノート番号）
+}
+
+#[derive(Debug, Clone, Copy)]
+// スティックごとの回転の速さと慣性の状態
+struct JogMotion {
+    velocity: f32,         // ステップ/秒
+    residual: f32,         // 送信しきれていないステップの端数
+    coasting: bool,        // 離した後に慣性で回っているかどうか
+    last_update: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)] // 既定のマッピングは固定のベロシティなので、DepthとSpeedはマッピングで選んだ時に使う
+// ノートオンのベロシティの決め方（Noteおよびスティックのノート）
+enum Velocity {
+    Fixed(u8),  // 常に同じベロシティ
+    Depth,      // アナログ値が増えなくなるまで待ち、その深さをベロシティにする
+    Speed { fastest_ms: u32, slowest_ms: u32 },  // アナログ値が動き始めてから閾値を越えるまでの速さ
+}
+
+impl Velocity {
+    // 0.0から1.0の値をベロシティ（1から127）に変換する
+    fn from_ratio(ratio: f32) -> u8 {
+        (1.0 + ratio.clamp(0.0, 1.0) * 126.0) as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+// ノートごとの押下状態
+enum PressState {
+    Released(Option<Instant>),  // 離されている（アナログ値が動き始めた時刻）
+    Pressing(f32),              // 閾値を越え、深さの確定待ち（これまでの最大値）
+    Sounding,                   // ノートオン送信済み
+}
+
+impl PressState {
+    // 押下状態を更新し、(ノートオンのベロシティ, ノートオフを送るか) を返す
+    fn update(&mut self, velocity: Velocity, pressed: bool, was_pressed: bool, analog: f32) -> (Option<u8>, bool) {
+        const REST: f32 = 0.05; // これを越えたら動き始めたとみなす
+        match *self {
+            PressState::Released(started) => {
+                if pressed && !was_pressed {
+                    match velocity {
+                        Velocity::Fixed(value) => {
+                            *self = PressState::Sounding;
+                            (Some(value), false)
+                        },
+                        Velocity::Depth => {
+                            *self = PressState::Pressing(analog);
+                            (None, false)
+                        },
+                        Velocity::Speed { fastest_ms, slowest_ms } => {
+                            // 1回のポーリングの間に閾値を越えた場合は最速とみなす
+                            let elapsed = started.map_or(0, |time| time.elapsed().as_millis() as u32);
+                            let ratio = slowest_ms.saturating_sub(elapsed) as f32 / slowest_ms.saturating_sub(fastest_ms).max(1) as f32;
+                            *self = PressState::Sounding;
+                            (Some(Velocity::from_ratio(ratio)), false)
+                        },
+                    }
+                } else {
+                    *self = PressState::Released(if analog > REST { started.or(Some(Instant::now())) } else { None });
+                    (None, false)
+                }
+            },
+            PressState::Pressing(peak) => {
+                if !pressed {
+                    // 深さが確定する前に離された
+                    *self = PressState::Released(None);
+                    (Some(Velocity::from_ratio(peak)), true)
+                } else if analog <= peak || analog >= 0.99 {
+                    *self = PressState::Sounding;
+                    (Some(Velocity::from_ratio(analog.max(peak))), false)
+                } else {
+                    *self = PressState::Pressing(analog);
+                    (None, false)
+                }
+            },
+            PressState::Sounding => {
+                if !pressed {
+                    *self = PressState::Released(None);
+                    (None, true)
+                } else {
+                    (None, false)
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)] // 既定のマッピングは直線なので、他のカーブはマッピングで選んだ時に使う
+// 送信前に値にかけるカーブ（CCAbsolute/Axis、0.0から1.0の値を変換する）
+enum ResponseCurve {
+    Linear,
+    Exponential,                     // 最初はゆっくり、最後に大きく変化する
+    Logarithmic,                     // 最初に大きく、最後はゆっくり変化する
+    SCurve,                          // 両端がゆっくり、中央で大きく変化する
+    Custom(&'static [(f32, f32)]),   // (入力, 出力) の折れ線（入力の昇順）
+}
+
+impl ResponseCurve {
+    fn apply(&self, value: f32) -> f32 {
+        const K: f32 = 5.0; // 指数/対数カーブの強さ
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            ResponseCurve::Linear => value,
+            ResponseCurve::Exponential => ((K * value).exp() - 1.0) / (K.exp() - 1.0),
+            ResponseCurve::Logarithmic => (1.0 + (K.exp() - 1.0) * value).ln() / K,
+            ResponseCurve::SCurve => value * value * (3.0 - 2.0 * value),
+            ResponseCurve::Custom(points) => {
+                // 入力が含まれる区間を線形補間する（範囲外は端の値）
+                let Some(&(first_x, first_y)) = points.first() else {
+                    return value;
+                };
+                if value <= first_x {
+                    return first_y;
+                }
+                for pair in points.windows(2) {
+                    let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+                    if value <= x1 {
+                        let t = if x1 > x0 { (value - x0) / (x1 - x0) } else { 1.0 };
+                        return y0 + (y1 - y0) * t;
+                    }
+                }
+                points[points.len() - 1].1
+            },
+        }.clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)] // 既定のマッピングはJumpなので、他の方法はマッピングで選んだ時に使う
+// スティックを倒し直した時や機能を切り替えた時に値が飛ばないようにする方法（CCAbsolute/Axis）
+enum Takeover {
+    Jump,    // スティックの角度をそのまま送信
+    Pickup,  // スティックの角度が最後に送信した値を通過するまで送信しない
+    Offset,  // 最後に送信した値を起点に、スティックの角度の変化分だけ動かす
+}
+
+#[derive(Debug, Clone, Copy)]
+// スティックごとのソフトテイクオーバーの状態
+enum TakeoverState {
+    Released,      // デッドゾーン内
+    Waiting(f32),  // 最後に送信した値の通過待ち（前回の角度と送信値の差）
+    Engaged(f32),  // 送信中（角度に加えるオフセット）
+}
+
+type ButtonGetter = fn(&ButtonState) -> bool;
+
 // CCマッピング用の構造体
 struct CCMapping {
-    button_getter: fn(&ButtonState) -> bool,
+    button_getter: ButtonGetter,
     cc_number: Option<u8>,
     note_number: Option<u8>,
     description: &'static str,
+    function_name: &'static str,  // スティックに割り当てられる機能の名前（UI表示用）
     deck: DeckType,
     behavior: Behavior,
+    output: Output,
+    takeover: Takeover,
+    encoding: RelativeEncoding,
+    steps_per_revolution: f32,           // CCRelativeのみ
+    acceleration: Option<Acceleration>,  // CCRelativeのみ
+    inertia: Option<Inertia>,            // CCRelativeのみ
+    curve: ResponseCurve,
+    default_value: Option<f32>,  // 既定値（0.0から1.0、カーブ適用前）
+    return_on_release: bool,     // スティックを離したら既定値に戻す（CCAbsoluteのみ）
+    reset_gesture: bool,         // スティック押し込みのダブルクリックで既定値に戻す
+    momentary: bool,             // 押している間だけこの機能に切り替え、離したら元の機能に戻す
+    auto_repeat: Option<AutoRepeat>,
+    velocity: Velocity,
+    analog_getter: Option<fn(&ControllerState) -> f32>,  // ベロシティ用のアナログ値（0.0から1.0、スティックでは倒した量を使う）
+}
+
+impl CCMapping {
+    // 省略されたフィールドの既定値
+    const DEFAULT: CCMapping = CCMapping {
+        button_getter: |_| false,
+        cc_number: None,
+        note_number: None,
+        description: "",
+        function_name: "",
+        deck: DeckType::Common,
+        behavior: Behavior::Note,
+        output: Output::ControlChange,
+        takeover: Takeover::Jump,
+        encoding: RelativeEncoding::TwosComplement,
+        steps_per_revolution: 360.0,
+        acceleration: None,
+        inertia: None,
+        curve: ResponseCurve::Linear,
+        default_value: None,
+        return_on_release: false,
+        reset_gesture: false,
+        momentary: false,
+        auto_repeat: None,
+        velocity: Velocity::Fixed(127),
+        analog_getter: None,
+    };
+}
+
+// 押している間、スティックの感度を下げる修飾ボタン
+#[allow(dead_code)] // 既定では割り当てていないので、MODIFIERSに追加した時だけ使う
+struct Modifier {
+    button_getter: ButtonGetter,
+    deck: DeckType,       // 感度を下げるスティック
+    relative_scale: f32,  // CCRelativeの一周あたりのステップ数にかける倍率
+    absolute_range: f32,  // CCAbsoluteのスティックの動きにかける倍率（押した時の値を中心に狭める）
 }
 
+#[derive(Debug, Clone, Copy)]
+// リセットジェスチャー用のクリックの記録
+struct LastClick {
+    time: Instant,
+    previous_cc: u8,  // クリックした時に割り当てられていたCC
+}
+
+// マッピングは静的に確保されているので、アドレスを状態管理用のキーにする
+fn mapping_key(mapping: &'static CCMapping) -> usize {
+    mapping as *const CCMapping as usize
+}
+
+// イコライザーやフィルターのノブの中央（Mixxxでは64が中央）
+const UNITY: f32 = 64.0 / 127.0;
+
+// ライブラリのスクロール用の加速（ゆっくり回すと1曲ずつ、素早く回すと最大8倍）
+const LIBRARY_ACCELERATION: Acceleration = Acceleration {
+    min_speed: PI,
+    max_speed: 6.0 * PI,
+    min_gain: 1.0,
+    max_gain: 8.0,
+    exponent: 2.0,
+};
+
 lazy_static::lazy_static! {
     static ref RUNNING: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
     // ボタンの押下時刻を記録
@@ -57,55 +507,127 @@ lazy_static::lazy_static! {
         (0.0, 0.0), // Left stick (x, y)
         (0.0, 0.0), // Right stick (x, y)
     ]));
+    // スティックの最後の位置を保存した時刻
+    static ref LAST_STICK_TIME: Arc<Mutex<[Instant; 2]>> = Arc::new(Mutex::new([Instant::now(); 2]));
+    // スティック押し込みの最後のクリック
+    static ref RESET_CLICK: Arc<Mutex<[Option<LastClick>; 2]>> = Arc::new(Mutex::new([None; 2]));
+    // ダブルクリック後、押し込みを離すまで固定するCC
+    static ref RESET_HOLD: Arc<Mutex<[Option<u8>; 2]>> = Arc::new(Mutex::new([None; 2]));
+    // 修飾ボタンを押した時の値（テイクオーバー適用後）
+    static ref FINE_ANCHOR: Arc<Mutex<[Option<f32>; 2]>> = Arc::new(Mutex::new([None; 2]));
+    // スティックごとの回転の速さと慣性の状態
+    static ref JOG_MOTION: Arc<Mutex<[JogMotion; 2]>> = Arc::new(Mutex::new([JogMotion {
+        velocity: 0.0,
+        residual: 0.0,
+        coasting: false,
+        last_update: Instant::now(),
+    }; 2]));
+    // スティックごとの相対値のピッチベンドを中央からずらしている送信先のデッキ
+    static ref PITCH_BEND_ACTIVE: Arc<Mutex<[Option<DeckType>; 2]>> = Arc::new(Mutex::new([None; 2]));
+    // スティックごとの弾いている方向（Flick用）
+    static ref FLICK_DIRECTION: Arc<Mutex<[Option<i32>; 2]>> = Arc::new(Mutex::new([None; 2]));
+    // スティックごとの最後に送信した段階（Detent用）
+    static ref LAST_DETENT: Arc<Mutex<[Option<u8>; 2]>> = Arc::new(Mutex::new([None; 2]));
+    // 一時的な切り替えの前に割り当てられていたCC
+    static ref MOMENTARY_PREVIOUS: Arc<Mutex<[Option<u8>; 2]>> = Arc::new(Mutex::new([None; 2]));
+    // ラジアルメニューの状態（None = 閉じている、Some(None) = 開いているが未選択）
+    static ref RADIAL_MENU: Arc<Mutex<[Option<Option<usize>>; 2]>> = Arc::new(Mutex::new([None; 2]));
+    // XYパッドのスティックが倒されているかどうか
+    static ref XY_PAD_ACTIVE: Arc<Mutex<[bool; 2]>> = Arc::new(Mutex::new([false; 2]));
     // 最後にスティックが倒されていたかどうか
     static ref STICK_NOTE_STATE: Arc<Mutex<[bool; 2]>> = Arc::new(Mutex::new([
         false, // Left stick note state (true = note on)
         false, // Right stick note state (true = note on)
     ]));
+    // ソフトテイクオーバーの状態
+    static ref TAKEOVER_STATE: Arc<Mutex<[TakeoverState; 2]>> = Arc::new(Mutex::new([TakeoverState::Released; 2]));
+    // (チャンネル, CC番号)ごとに最後に送信した値（0.0から1.0、カーブ適用前）
+    static ref LAST_SENT_VALUE: Arc<Mutex<HashMap<(u8, u8), f32>>> = Arc::new(Mutex::new(HashMap::new()));
+    // マッピングごとのノートの押下状態
+    static ref NOTE_PRESS_STATE: Arc<Mutex<HashMap<usize, PressState>>> = Arc::new(Mutex::new(HashMap::new()));
+    // マッピングごとのトグルの状態（true = オン）
+    static ref TOGGLE_STATE: Arc<Mutex<HashMap<usize, bool>>> = Arc::new(Mutex::new(HashMap::new()));
+    // マッピングごとのキーリピートの状態
+    static ref REPEAT_STATE: Arc<Mutex<HashMap<usize, RepeatState>>> = Arc::new(Mutex::new(HashMap::new()));
+    // マッピングごとの値の増減の状態
+    static ref RAMP_STATE: Arc<Mutex<HashMap<usize, RampState>>> = Arc::new(Mutex::new(HashMap::new()));
+    // マッピングごとのマクロの再生状態（押した時と離した時で別々）
+    static ref MACRO_RUNS: Arc<Mutex<HashMap<(usize, bool), MacroRun>>> = Arc::new(Mutex::new(HashMap::new()));
+    // デッキごとの送信先のMIDIチャンネル
+    static ref MIDI_CHANNELS: Arc<Mutex<MidiChannels>> = Arc::new(Mutex::new(Settings::get_settings().midi_channels()));
+    // 左右それぞれがデッキ3/4を操作しているかどうか
+    static ref DECK_FOCUS: Arc<Mutex<[bool; 2]>> = Arc::new(Mutex::new([false; 2]));
+    // 左右それぞれの操作していない方のデッキに割り当てられていたCC
+    static ref UNFOCUSED_DECK_CC: Arc<Mutex<[u8; 2]>> = Arc::new(Mutex::new([28; 2]));
+    // 左右のデッキを操作する側を入れ替えているかどうか（左利き用）
+    static ref SIDES_SWAPPED: Arc<Mutex<bool>> = Arc::new(Mutex::new(Settings::get_settings().swap_sides()));
+    // レイヤーAのトリガーをクロスフェーダーとして使うかどうか
+    static ref TRIGGER_CROSSFADER: Arc<Mutex<bool>> = Arc::new(Mutex::new(Settings::get_settings().trigger_crossfader()));
     // ボタンの最後の状態
     static ref LAST_BUTTON_STATE: Arc<Mutex<Option<ButtonState>>> = Arc::new(Mutex::new(None));
 
     // レイヤーA（通常時）のCCマッピング
     static ref CC_MAPPINGS_A: Vec<CCMapping> = vec![
         // 左デッキのマッピング
-        CCMapping { button_getter: |b| b.down, cc_number: Some(25), note_number: None, description: "Down", deck: DeckType::Left, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.left, cc_number: Some(26), note_number: None, description: "Left", deck: DeckType::Left, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.up, cc_number: Some(24), note_number: None, description: "Up", deck: DeckType::Left, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.right, cc_number: Some(23), note_number: None, description: "Right", deck: DeckType::Left, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.l, cc_number: Some(28), note_number: None, description: "L", deck: DeckType::Left, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.lt, cc_number: Some(9), note_number: None, description: "LT", deck: DeckType::Left, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.l_stick, cc_number: Some(6), note_number: Some(6), description: "L stick", deck: DeckType::Left, behavior: Behavior::CCRelative },
+        CCMapping { button_getter: |b| b.down, cc_number: Some(25), note_number: None, description: "Down", function_name: "EQ Lo", deck: DeckType::Left, behavior: Behavior::CCAbsolute, default_value: Some(UNITY), reset_gesture: true, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.left, cc_number: Some(26), note_number: None, description: "Left", function_name: "Filter", deck: DeckType::Left, behavior: Behavior::CCAbsolute, default_value: Some(UNITY), reset_gesture: true, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.up, cc_number: Some(24), note_number: None, description: "Up", function_name: "EQ Hi", deck: DeckType::Left, behavior: Behavior::CCAbsolute, default_value: Some(UNITY), reset_gesture: true, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.right, cc_number: Some(23), note_number: None, description: "Right", function_name: "Trim", deck: DeckType::Left, behavior: Behavior::CCAbsolute, default_value: Some(UNITY), reset_gesture: true, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.l, cc_number: Some(28), note_number: None, description: "L", function_name: "Volume", deck: DeckType::Left, behavior: Behavior::CCAbsolute, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.lt, cc_number: Some(9), note_number: None, description: "LT", function_name: "Tempo", deck: DeckType::Left, behavior: Behavior::CCAbsolute, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.l_stick, cc_number: Some(6), note_number: Some(6), description: "L stick", function_name: "Jog", deck: DeckType::Left, behavior: Behavior::CCRelative, ..CCMapping::DEFAULT },
         
         // 右デッキのマッピング
-        CCMapping { button_getter: |b| b.south, cc_number: Some(25), note_number: None, description: "South", deck: DeckType::Right, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.east, cc_number: Some(26), note_number: None, description: "East", deck: DeckType::Right, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.north, cc_number: Some(24), note_number: None, description: "North", deck: DeckType::Right, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.west, cc_number: Some(23), note_number: None, description: "West", deck: DeckType::Right, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.r, cc_number: Some(28), note_number: None, description: "R", deck: DeckType::Right, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.rt, cc_number: Some(9), note_number: None, description: "RT", deck: DeckType::Right, behavior: Behavior::CCAbsolute },
-        CCMapping { button_getter: |b| b.r_stick, cc_number: Some(6), note_number: Some(6), description: "R stick", deck: DeckType::Right, behavior: Behavior::CCRelative },
+        CCMapping { button_getter: |b| b.south, cc_number: Some(25), note_number: None, description: "South", function_name: "EQ Lo", deck: DeckType::Right, behavior: Behavior::CCAbsolute, default_value: Some(UNITY), reset_gesture: true, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.east, cc_number: Some(26), note_number: None, description: "East", function_name: "Filter", deck: DeckType::Right, behavior: Behavior::CCAbsolute, default_value: Some(UNITY), reset_gesture: true, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.north, cc_number: Some(24), note_number: None, description: "North", function_name: "EQ Hi", deck: DeckType::Right, behavior: Behavior::CCAbsolute, default_value: Some(UNITY), reset_gesture: true, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.west, cc_number: Some(23), note_number: None, description: "West", function_name: "Trim", deck: DeckType::Right, behavior: Behavior::CCAbsolute, default_value: Some(UNITY), reset_gesture: true, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.r, cc_number: Some(28), note_number: None, description: "R", function_name: "Volume", deck: DeckType::Right, behavior: Behavior::CCAbsolute, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.rt, cc_number: Some(9), note_number: None, description: "RT", function_name: "Tempo", deck: DeckType::Right, behavior: Behavior::CCAbsolute, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.r_stick, cc_number: Some(6), note_number: Some(6), description: "R stick", function_name: "Jog", deck: DeckType::Right, behavior: Behavior::CCRelative, ..CCMapping::DEFAULT },
     ];
 
     // レイヤーB（スタート/セレクトボタン押下時）のCCマッピング
     static ref CC_MAPPINGS_B: Vec<CCMapping> = vec![
         // 左デッキのマッピング
-        CCMapping { button_getter: |b| b.down, cc_number: None, note_number: Some(0), description: "Down (Note 0)", deck: DeckType::Left, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.left, cc_number: None, note_number: Some(1), description: "Left (Note 1)", deck: DeckType::Left, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.up, cc_number: None, note_number: Some(27), description: "Up (Note 27)", deck: DeckType::Left, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.right, cc_number: None, note_number: Some(2), description: "Right (Note 2)", deck: DeckType::Left, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.l, cc_number: None, note_number: Some(20), description: "L (Note 20)", deck: DeckType::Left, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.lt, cc_number: None, note_number: Some(21), description: "LT (Note 21)", deck: DeckType::Left, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.l_stick, cc_number: None, note_number: Some(7), description: "L stick (Note 7)", deck: DeckType::Common, behavior: Behavior::Note },
+        CCMapping { button_getter: |b| b.down, cc_number: None, note_number: Some(0), description: "Down (Note 0)", deck: DeckType::Left, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.left, cc_number: None, note_number: Some(1), description: "Left (Note 1)", deck: DeckType::Left, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.up, cc_number: None, note_number: Some(27), description: "Up (Note 27)", deck: DeckType::Left, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.right, cc_number: None, note_number: Some(2), description: "Right (Note 2)", deck: DeckType::Left, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.l, cc_number: None, note_number: Some(20), description: "L (Note 20)", deck: DeckType::Left, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.lt, cc_number: None, note_number: Some(21), description: "LT (Note 21)", deck: DeckType::Left, behavior: Behavior::Note, analog_getter: Some(|s| s.triggers.left), ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.l_stick, cc_number: None, note_number: Some(7), description: "L stick (Note 7)", deck: DeckType::Common, behavior: Behavior::Note, ..CCMapping::DEFAULT },
         
         // 右デッキのマッピング
-        CCMapping { button_getter: |b| b.south, cc_number: None, note_number: Some(0), description: "A (Note 0)", deck: DeckType::Right, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.east, cc_number: None, note_number: Some(1), description: "B (Note 2)", deck: DeckType::Right, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.north, cc_number: None, note_number: Some(27), description: "Y (Note 27)", deck: DeckType::Right, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.west, cc_number: None, note_number: Some(2), description: "X (Note 1)", deck: DeckType::Right, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.r, cc_number: None, note_number: Some(20), description: "R (Note 20)", deck: DeckType::Right, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.rt, cc_number: None, note_number: Some(21), description: "RT (Note 21)", deck: DeckType::Right, behavior: Behavior::Note },
-        CCMapping { button_getter: |b| b.r_stick, cc_number: None, note_number: Some(7), description: "R stick (Note 7)", deck: DeckType::Common, behavior: Behavior::Note },
+        CCMapping { button_getter: |b| b.south, cc_number: None, note_number: Some(0), description: "A (Note 0)", deck: DeckType::Right, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.east, cc_number: None, note_number: Some(1), description: "B (Note 2)", deck: DeckType::Right, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.north, cc_number: None, note_number: Some(27), description: "Y (Note 27)", deck: DeckType::Right, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.west, cc_number: None, note_number: Some(2), description: "X (Note 1)", deck: DeckType::Right, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.r, cc_number: None, note_number: Some(20), description: "R (Note 20)", deck: DeckType::Right, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.rt, cc_number: None, note_number: Some(21), description: "RT (Note 21)", deck: DeckType::Right, behavior: Behavior::Note, analog_getter: Some(|s| s.triggers.right), ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.r_stick, cc_number: None, note_number: Some(7), description: "R stick (Note 7)", deck: DeckType::Common, behavior: Behavior::Note, ..CCMapping::DEFAULT },
+    ];
+
+    // レイヤーC（スタートとセレクトの同時押し時）のCCマッピング
+    static ref CC_MAPPINGS_C: Vec<CCMapping> = vec![
+        CCMapping { button_getter: |b| b.lt, cc_number: None, note_number: None, description: "LT (Deck select)", deck: DeckType::Left, behavior: Behavior::DeckSelect, ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |b| b.rt, cc_number: None, note_number: None, description: "RT (Deck select)", deck: DeckType::Right, behavior: Behavior::DeckSelect, ..CCMapping::DEFAULT },
+    ];
+
+    // レイヤーBのスティックのマッピング（左スティック、右スティックの順、button_getterは使わない）
+    // 既定ではどちらもライブラリの曲選択（CC 0、一周12ステップ）
+    static ref STICK_MAPPINGS_B: [CCMapping; 2] = [
+        CCMapping { button_getter: |_| false, cc_number: Some(0), note_number: None, description: "L stick", function_name: "Library", deck: DeckType::Common, behavior: Behavior::CCRelative, steps_per_revolution: 12.0, acceleration: Some(LIBRARY_ACCELERATION), ..CCMapping::DEFAULT },
+        CCMapping { button_getter: |_| false, cc_number: Some(0), note_number: None, description: "R stick", function_name: "Library", deck: DeckType::Common, behavior: Behavior::CCRelative, steps_per_revolution: 12.0, acceleration: Some(LIBRARY_ACCELERATION), ..CCMapping::DEFAULT },
     ];
+
+    // トリガーをクロスフェーダーとして使う場合にレイヤーAに加えるマッピング（LT/RTでテンポは選べなくなる）
+    static ref CROSSFADER_MAPPING: CCMapping = CCMapping { button_getter: |_| false, cc_number: Some(8), note_number: None, description: "LT/RT (Crossfader)", deck: DeckType::Common, behavior: Behavior::Crossfader { curve: CrossfaderCurve::Smooth, cut: false }, ..CCMapping::DEFAULT };
+
+    // 修飾ボタン（既定ではどのボタンも他の操作に使っているので割り当てなし）
+    // 使う場合は、マッピングで他の操作に使っていないボタンを割り当てる
+    // 例: Modifier { button_getter: |b| b.lt, deck: DeckType::Left, relative_scale: 0.25, absolute_range: 0.25 }
+    static ref MODIFIERS: Vec<Modifier> = vec![];
 }
 
 // スタート/セレクトボタンの短押し判定（500ms以内）
@@ -113,12 +635,50 @@ fn is_quick_press(press_time: Option<Instant>) -> bool {
     press_time.map_or(false, |time| time.elapsed().as_millis() < 500)
 }
 
-fn get_active_mappings(state: &ControllerState) -> &'static Vec<CCMapping> {
-    if state.buttons.start || state.buttons.select {
-        &CC_MAPPINGS_B
+fn get_active_mappings(state: &ControllerState) -> Vec<&'static CCMapping> {
+    if state.buttons.start && state.buttons.select {
+        CC_MAPPINGS_C.iter().collect()
+    } else if state.buttons.start || state.buttons.select {
+        CC_MAPPINGS_B.iter().collect()
+    } else if *TRIGGER_CROSSFADER.lock().unwrap() {
+        CC_MAPPINGS_A.iter().chain(std::iter::once(&*CROSSFADER_MAPPING)).collect()
     } else {
-        &CC_MAPPINGS_A
+        CC_MAPPINGS_A.iter().collect()
+    }
+}
+
+// クロスフェーダーがあるレイヤーでは、トリガーは他のマッピングや修飾ボタンでは押されていないものとして扱う
+fn without_crossfader_triggers(mappings: &[&CCMapping], buttons: &ButtonState) -> ButtonState {
+    let mut buttons = buttons.clone();
+    if mappings.iter().any(|m| matches!(m.behavior, Behavior::Crossfader { .. })) {
+        buttons.lt = false;
+        buttons.rt = false;
+    }
+    buttons
+}
+
+fn get_stick_position(state: &ControllerState, deck: DeckType) -> (f32, f32) {
+    let [x, y] = match deck {
+        DeckType::Left => state.sticks.left,
+        DeckType::Right => state.sticks.right,
+        DeckType::Common => unreachable!("DeckType common doesn't have a stick"),
+    };
+    (x, y)
+}
+
+fn is_radial_menu_open(deck: DeckType) -> bool {
+    RADIAL_MENU.lock().unwrap()[deck.stick_index()].is_some()
+}
+
+// スティックを倒した方向から選択肢の番号を求める（12時方向を中心とした扇形が0番、時計回り）
+fn calculate_radial_sector(x: f32, y: f32, deadzone: f32, count: usize) -> Option<usize> {
+    let distance = (x * x + y * y).sqrt();
+    if distance < deadzone || count == 0 {
+        return None;
     }
+    let angle = f32::atan2(x, y).rem_euclid(2.0 * PI);
+    let sector_size = 2.0 * PI / count as f32;
+    Some(((angle / sector_size).round() as usize) % count)
 }
 
 fn get_current_cc(deck: DeckType) -> u8 {
@@ -146,6 +706,8 @@ pub fn start_mapping() -> Sender<ControllerState> {
         let mut guard = running.lock().unwrap();
         *guard = true;
     }
+    // 設定で変更されたMIDIチャンネルを反映
+    *MIDI_CHANNELS.lock().unwrap() = Settings::get_settings().midi_channels();
 
     println!("\nInitial CC mappings:");
     println!("Left deck: CC#{}", get_current_cc(DeckType::Left));
@@ -188,9 +750,12 @@ pub fn start_mapping() -> Sender<ControllerState> {
 pub fn stop_mapping() {
     let mut running = RUNNING.lock().unwrap();
     *running = false;
+    // 次回の開始時にはすべてのトグルをオフから始める
+    TOGGLE_STATE.lock().unwrap().clear();
 }
 
-fn calculate_midi_cc_value_absolute(x: f32, y: f32, deadzone: f32) -> Option<u8> {
+// スティックの角度を0.0から1.0の値に変換する
+fn calculate_stick_value_absolute(x: f32, y: f32, deadzone: f32) -> Option<f32> {
     let distance = (x * x + y * y).sqrt();
     
     if distance < deadzone {
@@ -202,19 +767,50 @@ fn calculate_midi_cc_value_absolute(x: f32, y: f32, deadzone: f32) -> Option<u8>
     let angle = f32::atan2(x, y);
     
     // 角度を0.0から1.0の範囲に正規化（8時付近で0.0、12時方向が0.5、4時付近で1.0）
-    let mut value = ((angle / PI) * 1.3 / 2.0) + 0.5;
+    let value = ((angle / PI) * 1.3 / 2.0) + 0.5;
 
     // 値を0.0から1.0の範囲にクリップ
-    value = value.max(0.0).min(1.0);
+    Some(value.max(0.0).min(1.0))
+}
+
+// スティックの角度をcount段階に区切る
+// 境界付近でばたつかないよう、前回の段階から少しはみ出すまでは前回の段階とみなす
+fn calculate_stick_detent(x: f32, y: f32, deadzone: f32, count: u8, last_detent: Option<u8>) -> Option<u8> {
+    const HYSTERESIS: f32 = 0.2; // 1段階の幅に対する割合
+    let value = calculate_stick_value_absolute(x, y, deadzone)?;
+    let position = value * count as f32;
+
+    if let Some(last_detent) = last_detent {
+        let last = last_detent as f32;
+        if position >= last - HYSTERESIS && position < last + 1.0 + HYSTERESIS {
+            return Some(last_detent);
+        }
+    }
+    Some((position as u8).min(count.saturating_sub(1)))
+}
+
+// スティックの片方の軸の倒し具合を0.0から1.0の値に変換する
+// デッドゾーンの端が0.5になるように詰めるので、中央の値にも合わせられる
+fn calculate_stick_value_axis(x: f32, y: f32, axis: StickAxis, deadzone: f32) -> Option<f32> {
+    let position = match axis {
+        StickAxis::X => x,
+        StickAxis::Y => y,
+    };
+
+    if position.abs() < deadzone {
+        return None;
+    }
 
-    // MIDI値に変換（0-127）
-    Some((value * 127.0) as u8)
+    let amount = ((position.abs() - deadzone) / (1.0 - deadzone)).min(1.0);
+    Some(0.5 + 0.5 * amount * position.signum())
 }
 
-fn calculate_midi_cc_value_relative(x: f32, y: f32, deck: DeckType, deadzone: f32, steps: f32) -> Option<u8> {
+// 前回からのスティックの回転量をステップ数（符号付き）に変換する
+fn calculate_relative_steps(x: f32, y: f32, deck: DeckType, deadzone: f32, steps: f32, acceleration: Option<Acceleration>) -> Option<i32> {
     let distance = (x * x + y * y).sqrt();
     let angle = f32::atan2(x, y);
     let mut last_stick_pos = LAST_STICK_POS.lock().unwrap();
+    let mut last_stick_time = LAST_STICK_TIME.lock().unwrap();
     let stick_idx = match deck {
         DeckType::Left => 0,
         DeckType::Right => 1,
@@ -224,6 +820,7 @@ fn calculate_midi_cc_value_relative(x: f32, y: f32, deck: DeckType, deadzone: f3
     // デッドゾーン内の場合は現在の位置を保存して終了
     if distance < deadzone {
         last_stick_pos[stick_idx] = (x, y);
+        last_stick_time[stick_idx] = Instant::now();
         return None;
     }
     
@@ -240,24 +837,195 @@ fn calculate_midi_cc_value_relative(x: f32, y: f32, deck: DeckType, deadzone: f3
     }
     
     // 一周をstepsステップとして正規化
-    let normalized = diff / (PI * 2.0) * steps;
-    let mut value = normalized.trunc().min(127.0).max(-127.0) as i32;
-    if value < 0 {
-        value += 128;
+    let mut normalized = diff / (PI * 2.0) * steps;
+
+    // 前回保存してからの平均の回転速度に応じて加速
+    if let Some(acceleration) = acceleration {
+        let elapsed = last_stick_time[stick_idx].elapsed().as_secs_f32().max(0.001);
+        normalized *= acceleration.gain(diff.abs() / elapsed);
     }
+
+    let value = normalized.trunc().min(127.0).max(-127.0) as i32;
     if value == 0 {
-        return None;
+        None
     } else {
         // 現在の角度を保存
         last_stick_pos[stick_idx] = (x, y);
-        return Some(value as u8);
+        last_stick_time[stick_idx] = Instant::now();
+        Some(value)
+    }
+}
+
+// 回転の速さを記録し、スティックが離された後は慣性で送信するステップ数を返す
+fn update_inertia(deck: DeckType, inertia: Inertia, engaged: bool, steps: Option<i32>) -> Option<i32> {
+    const STOP_SPEED: f32 = 1.0; // この速さを下回ったら止める
+    let mut jog_motion = JOG_MOTION.lock().unwrap();
+    let motion = &mut jog_motion[deck.stick_index()];
+    let now = Instant::now();
+    let dt = now.duration_since(motion.last_update).as_secs_f32().clamp(0.001, 0.1);
+    motion.last_update = now;
+
+    if engaged {
+        // 回している間は速さを平滑化して記録する（再び触れたら慣性は止まる）
+        let current_velocity = steps.unwrap_or(0) as f32 / dt;
+        motion.velocity = motion.velocity * 0.5 + current_velocity * 0.5;
+        motion.coasting = false;
+        motion.residual = 0.0;
+        return None;
+    }
+
+    if !motion.coasting {
+        if motion.velocity.abs() < inertia.min_speed {
+            motion.velocity = 0.0;
+            return None;
+        }
+        // 素早く回したまま離された
+        motion.coasting = true;
+    }
+
+    motion.velocity *= (-inertia.friction * dt).exp();
+    if motion.velocity.abs() < STOP_SPEED {
+        motion.velocity = 0.0;
+        motion.coasting = false;
+        return None;
+    }
+    motion.residual += motion.velocity * dt;
+    let steps = motion.residual.trunc();
+    motion.residual -= steps;
+    if steps == 0.0 {
+        None
+    } else {
+        Some(steps as i32)
+    }
+}
+
+// 慣性を止め、記録した回転の速さを捨てる
+fn reset_jog_motion(deck: DeckType) {
+    let mut jog_motion = JOG_MOTION.lock().unwrap();
+    let motion = &mut jog_motion[deck.stick_index()];
+    motion.velocity = 0.0;
+    motion.residual = 0.0;
+    motion.coasting = false;
+    motion.last_update = Instant::now();
+}
+
+// 慣性で回っている（または離した瞬間に回り始める）かどうか
+fn is_coasting(deck: DeckType, inertia: Inertia) -> bool {
+    let motion = JOG_MOTION.lock().unwrap()[deck.stick_index()];
+    motion.coasting || motion.velocity.abs() >= inertia.min_speed
+}
+
+// ソフトテイクオーバーを適用し、送信すべき値を返す
+// stickは操作しているスティック、deckは送信先のデッキ
+fn apply_takeover(stick: DeckType, deck: DeckType, control_number: u8, takeover: Takeover, value: Option<f32>) -> Option<f32> {
+    const PICKUP_THRESHOLD: f32 = 0.02; // この差以内に近づいたら通過したとみなす
+    let mut takeover_state = TAKEOVER_STATE.lock().unwrap();
+    let state = &mut takeover_state[stick.stick_index()];
+
+    let Some(value) = value else {
+        *state = TakeoverState::Released;
+        return None;
+    };
+    let last_value = LAST_SENT_VALUE.lock().unwrap().get(&(deck.midi_channel(), control_number)).copied();
+
+    // スティックが倒された瞬間
+    if let TakeoverState::Released = state {
+        *state = match (takeover, last_value) {
+            (Takeover::Pickup, Some(last_value)) => TakeoverState::Waiting(value - last_value),
+            (Takeover::Offset, Some(last_value)) => TakeoverState::Engaged(last_value - value),
+            _ => TakeoverState::Engaged(0.0),
+        };
+    }
+
+    match *state {
+        TakeoverState::Waiting(last_diff) => {
+            let diff = value - last_value.unwrap_or(value);
+            if diff.abs() < PICKUP_THRESHOLD || diff.signum() != last_diff.signum() {
+                *state = TakeoverState::Engaged(0.0);
+                Some(value)
+            } else {
+                *state = TakeoverState::Waiting(diff);
+                None
+            }
+        },
+        TakeoverState::Engaged(offset) => Some((value + offset).clamp(0.0, 1.0)),
+        TakeoverState::Released => None,
+    }
+}
+
+fn reset_takeover(deck: DeckType) {
+    TAKEOVER_STATE.lock().unwrap()[deck.stick_index()] = TakeoverState::Released;
+}
+
+// スティックの機能のCC番号に対応するマッピングを取得する
+fn find_function_mapping(deck: DeckType, control_number: u8) -> Option<&'static CCMapping> {
+    CC_MAPPINGS_A.iter().find(|m| m.cc_number == Some(control_number) && m.deck == deck)
+}
+
+// ボタン以外の方法（ラジアルメニューなど）でスティックの機能を切り替える
+fn select_function(deck: DeckType, control_number: u8, last_left_cc: &mut u8, last_right_cc: &mut u8) {
+    let description = find_function_mapping(deck, control_number).map_or("", |m| m.description);
+    match deck {
+        DeckType::Left => {
+            update_cc_if_changed(DeckType::Left, control_number, description, last_left_cc);
+        },
+        DeckType::Right => {
+            update_cc_if_changed(DeckType::Right, control_number, description, last_right_cc);
+        },
+        DeckType::Common => (),
+    }
+}
+
+// その側で操作するデッキを切り替え、切り替え先のデッキに割り当てていた機能に戻す
+fn toggle_deck_focus(deck: DeckType, last_control_number: &mut u8) {
+    let idx = deck.stick_index();
+    // 操作中のスティックのノートとピッチベンドは切り替え前のデッキで止め、慣性も引き継がない
+    release_stick_note(deck, *last_control_number);
+    release_pitch_bend(deck);
+    reset_takeover(deck);
+    reset_jog_motion(deck);
+    let restored_cc = std::mem::replace(&mut UNFOCUSED_DECK_CC.lock().unwrap()[idx], *last_control_number);
+    let mut deck_focus = DECK_FOCUS.lock().unwrap();
+    deck_focus[idx] = !deck_focus[idx];
+    drop(deck_focus);
+    println!("{} side now controls deck {}", deck.name(), deck.deck_index() + 1);
+    update_cc_if_changed(deck, restored_cc, "Deck select", last_control_number);
+}
+
+// 相対値のピッチベンドは中央からのずれとして送信しているので、止まったら中央に戻す
+fn release_pitch_bend(deck: DeckType) {
+    if let Some(output_deck) = PITCH_BEND_ACTIVE.lock().unwrap()[deck.stick_index()].take() {
+        if let Err(e) = send_pitch_bend(output_deck.midi_channel(), 8192) {
+            eprintln!("Failed to send MIDI PitchBend ({} Deck): {:?}", output_deck.name(), e);
+        }
+    }
+}
+
+// スティックのノートが鳴っていれば止める
+fn release_stick_note(deck: DeckType, control_number: u8) {
+    let mut note_state = STICK_NOTE_STATE.lock().unwrap();
+    if note_state[deck.stick_index()] {
+        note_state[deck.stick_index()] = false;
+        if let Some(mapping) = find_function_mapping(deck, control_number) {
+            if let Some(note_number) = mapping.note_number {
+                process_note(mapping, deck, note_number, false, true, 0.0);
+            }
+        }
     }
 }
 
 fn update_cc_if_changed(deck: DeckType, new_control_number: u8, description: &str, last_control_number: &mut u8) -> bool {
     if new_control_number != *last_control_number {
+        // スティックを倒したまま切り替えた場合は、切り替え前の機能のノートとピッチベンドを止める
+        release_stick_note(deck, *last_control_number);
+        release_pitch_bend(deck);
+
         *last_control_number = new_control_number;
         set_current_cc(deck, new_control_number);
+        // 切り替え先の機能で改めてテイクオーバーを行い、切り替え前の慣性は引き継がない
+        reset_takeover(deck);
+        reset_jog_motion(deck);
+        LAST_DETENT.lock().unwrap()[deck.stick_index()] = None;
         println!("{} deck control number changed to: {} ({})", 
             match deck {
                 DeckType::Left => "Left",
@@ -273,79 +1041,379 @@ fn update_cc_if_changed(deck: DeckType, new_control_number: u8, description: &st
     }
 }
 
-fn process_stick(x: f32, y: f32, control_number: u8, deck: DeckType, deadzone_cc: f32, deadzone_on: f32, deadzone_off: f32) {
+// スティックのデッドゾーン
+struct Deadzones {
+    cc: f32,        // CC用
+    note_on: f32,   // ノートオン用
+    note_off: f32,  // ノートオフ用
+    axis: f32,      // 軸ごとの操作用
+}
+
+// 押されている修飾ボタンを取得する
+fn get_active_modifier(deck: DeckType, buttons: &ButtonState) -> Option<&'static Modifier> {
+    MODIFIERS.iter().find(|m| m.deck == deck && (m.button_getter)(buttons))
+}
+
+// 修飾ボタンが押されている間、押した時の値を中心にスティックの動きを狭める
+// rawはテイクオーバー適用前、valueは適用後の値
+fn apply_fine_adjustment(stick: DeckType, deck: DeckType, control_number: u8, modifier: Option<&Modifier>, raw: Option<f32>, value: Option<f32>) -> Option<f32> {
+    let mut fine_anchor = FINE_ANCHOR.lock().unwrap();
+    let anchor = &mut fine_anchor[stick.stick_index()];
+    match (modifier, raw, value) {
+        (Some(modifier), _, Some(value)) => {
+            let center = *anchor.get_or_insert(value);
+            Some((center + (value - center) * modifier.absolute_range).clamp(0.0, 1.0))
+        },
+        (None, Some(raw), Some(value)) => {
+            if anchor.take().is_some() {
+                // 修飾ボタンが離された瞬間は、最後に送信した値から続けて動かす
+                let last_value = LAST_SENT_VALUE.lock().unwrap().get(&(deck.midi_channel(), control_number)).copied().unwrap_or(value);
+                TAKEOVER_STATE.lock().unwrap()[stick.stick_index()] = TakeoverState::Engaged(last_value - raw);
+                Some(last_value)
+            } else {
+                Some(value)
+            }
+        },
+        _ => {
+            *anchor = None;
+            value
+        },
+    }
+}
+
+// deckは操作しているスティック、送信先はマッピングのデッキ（レイヤーBではCommonのこともある）
+fn process_stick(x: f32, y: f32, mapping: &'static CCMapping, deck: DeckType, deadzones: &Deadzones, modifier: Option<&Modifier>) {
     let distance = (x * x + y * y).sqrt();
-    let stick_idx = match deck {
-        DeckType::Left => 0,
-        DeckType::Right => 1,
-        DeckType::Common => unreachable!("DeckType common doesn't have a stick"),
+    let stick_idx = deck.stick_index();
+    let output_deck = mapping.deck;
+    let Some(control_number) = mapping.cc_number else {
+        return;
     };
 
-    // 現在のCCに対応するマッピングを取得
-    let mapping = CC_MAPPINGS_A.iter()
-        .find(|m| m.cc_number.map_or(false, |cc| cc == control_number) && m.deck == deck);
+    // ノート処理
+    if let Some(note_number) = mapping.note_number {
+        let mut note_state = STICK_NOTE_STATE.lock().unwrap();
+        let is_pressed = if note_state[stick_idx] {
+            // 慣性で回っている間はノートを離さない
+            distance >= deadzones.note_off || mapping.inertia.is_some_and(|inertia| is_coasting(deck, inertia))
+        } else {
+            distance > deadzones.note_on
+        };
+        let was_pressed = note_state[stick_idx];
+        note_state[stick_idx] = is_pressed;
+        process_note(mapping, output_deck, note_number, is_pressed, was_pressed, distance.min(1.0));
+    }
 
-    if let Some(mapping) = mapping {
-        // ノート処理
-        if let Some(note_number) = mapping.note_number {
-            let mut note_state = STICK_NOTE_STATE.lock().unwrap();
-            let is_pressed = if note_state[stick_idx] {
-                distance >= deadzone_off
+    // CC処理
+    match mapping.behavior {
+        Behavior::CCAbsolute => {
+            let value = calculate_stick_value_absolute(x, y, deadzones.cc);
+            let was_engaged = !matches!(TAKEOVER_STATE.lock().unwrap()[stick_idx], TakeoverState::Released);
+            let adjusted = apply_takeover(deck, output_deck, control_number, mapping.takeover, value);
+            if let Some(value) = apply_fine_adjustment(deck, output_deck, control_number, modifier, value, adjusted) {
+                send_absolute_value(output_deck, control_number, mapping.output, mapping.curve, value);
+            } else if value.is_none() && was_engaged && mapping.return_on_release {
+                // スティックが離された瞬間
+                if let Some(default_value) = mapping.default_value {
+                    send_absolute_value(output_deck, control_number, mapping.output, mapping.curve, default_value);
+                }
+            }
+        },
+        Behavior::CCRelative => {
+            let resolution = mapping.steps_per_revolution * modifier.map_or(1.0, |m| m.relative_scale);
+            let steps = calculate_relative_steps(x, y, deck, deadzones.cc, resolution, mapping.acceleration);
+            let inertia_steps = mapping.inertia.and_then(|inertia| update_inertia(deck, inertia, distance >= deadzones.cc, steps));
+            if let Some(steps) = steps.or(inertia_steps) {
+                send_relative_steps(output_deck, control_number, mapping.output, mapping.encoding, steps);
+                if matches!(mapping.output, Output::PitchBend) {
+                    PITCH_BEND_ACTIVE.lock().unwrap()[stick_idx] = Some(output_deck);
+                }
             } else {
-                distance > deadzone_on
+                // ピッチベンドは回した分だけずらすので、ステップが出なかったポーリングでは中央に戻す
+                release_pitch_bend(deck);
+            }
+        },
+        Behavior::Axis { axis, spring_return } => {
+            let value = calculate_stick_value_axis(x, y, axis, deadzones.axis);
+            let was_engaged = !matches!(TAKEOVER_STATE.lock().unwrap()[stick_idx], TakeoverState::Released);
+            if let Some(value) = apply_takeover(deck, output_deck, control_number, mapping.takeover, value) {
+                send_absolute_value(output_deck, control_number, mapping.output, mapping.curve, value);
+            } else if value.is_none() && was_engaged {
+                // スティックが離された瞬間
+                if let Some(center) = spring_return {
+                    send_absolute_value(output_deck, control_number, mapping.output, mapping.curve, center);
+                }
+            }
+        },
+        Behavior::Flick { axis } => {
+            // 弾いた方向の軸が大きく倒された時に1回だけ送信し、デッドゾーンに戻るまでは送信しない
+            let mut flick_direction = FLICK_DIRECTION.lock().unwrap();
+            let (along, across) = match axis {
+                StickAxis::X => (x, y),
+                StickAxis::Y => (y, x),
             };
+            if distance < deadzones.note_off {
+                flick_direction[stick_idx] = None;
+            } else if along.abs() >= deadzones.cc && along.abs() > across.abs() && flick_direction[stick_idx].is_none() {
+                let direction = if along > 0.0 { 1 } else { -1 };
+                flick_direction[stick_idx] = Some(direction);
+                send_relative_steps(output_deck, control_number, mapping.output, mapping.encoding, direction);
+            }
+        },
+        Behavior::Detent { count, base_note } => {
+            let mut last_detent = LAST_DETENT.lock().unwrap();
+            let detent = calculate_stick_detent(x, y, deadzones.cc, count, last_detent[stick_idx]);
+            if let Some(detent) = detent.filter(|d| Some(*d) != last_detent[stick_idx]) {
+                last_detent[stick_idx] = Some(detent);
+                let result = match base_note {
+                    // ノート番号が127を超える段階は送信しない
+                    Some(base_note) => match base_note.checked_add(detent).filter(|note| *note <= 127) {
+                        Some(note) => send_note_on(output_deck.midi_channel(), note, 127)
+                            .and_then(|_| send_note_off(output_deck.midi_channel(), note)),
+                        None => Ok(()),
+                    },
+                    None => send_cc_change(output_deck.midi_channel(), control_number, (detent as u32 * 127 / (count.max(2) as u32 - 1)) as u8),
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to send MIDI Detent ({} Deck): {:?}", output_deck.name(), e);
+                }
+                // 段階が変わったことを振動で知らせる
+                request_rumble(Rumble::TICK);
+            }
+        },
+        Behavior::XYPad { x_cc, y_cc, latch } => {
+            let mut xy_pad_active = XY_PAD_ACTIVE.lock().unwrap();
+            if distance >= deadzones.axis {
+                xy_pad_active[stick_idx] = true;
+                // デッドゾーンの縁が中央（0.5）になるよう、デッドゾーンの外側を全域に広げる
+                let scale = ((distance - deadzones.axis) / (1.0 - deadzones.axis)).min(1.0) / distance;
+                send_absolute_value_if_changed(output_deck, x_cc, Output::ControlChange, mapping.curve, ((x * scale + 1.0) / 2.0).clamp(0.0, 1.0));
+                send_absolute_value_if_changed(output_deck, y_cc, Output::ControlChange, mapping.curve, ((y * scale + 1.0) / 2.0).clamp(0.0, 1.0));
+            } else if xy_pad_active[stick_idx] {
+                // スティックが離された瞬間
+                xy_pad_active[stick_idx] = false;
+                if !latch {
+                    send_absolute_value(output_deck, x_cc, Output::ControlChange, mapping.curve, 0.5);
+                    send_absolute_value(output_deck, y_cc, Output::ControlChange, mapping.curve, 0.5);
+                }
+            }
+        },
+        _ => (),
+    }
+}
+
+// 0.0から1.0の値をカーブに通し、出力先の分解能に合わせて送信する
+fn send_absolute_value(deck: DeckType, control_number: u8, output: Output, curve: ResponseCurve, value: f32) {
+    let curved = curve.apply(value);
+    let result = match output {
+        Output::ControlChange => send_cc_change(deck.midi_channel(), control_number, output.quantize(curved) as u8),
+        Output::Parameter(parameter) => send_parameter_value(deck.midi_channel(), parameter, output.quantize(curved)),
+        Output::PitchBend => send_pitch_bend(deck.midi_channel(), output.quantize(curved)),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to send MIDI {:?} ({} Deck): {:?}", output, deck.name(), e);
+    }
+    LAST_SENT_VALUE.lock().unwrap().insert((deck.midi_channel(), control_number), value);
+}
+
+// 送信する値が最後に送信した値から変わる時だけ送信する
+fn send_absolute_value_if_changed(deck: DeckType, control_number: u8, output: Output, curve: ResponseCurve, value: f32) {
+    let last_value = LAST_SENT_VALUE.lock().unwrap().get(&(deck.midi_channel(), control_number)).copied();
+    if last_value.map(|last| output.quantize(curve.apply(last))) != Some(output.quantize(curve.apply(value))) {
+        send_absolute_value(deck, control_number, output, curve, value);
+    }
+}
+
+fn send_relative_steps(deck: DeckType, control_number: u8, output: Output, encoding: RelativeEncoding, steps: i32) {
+    let result = match output {
+        Output::ControlChange => send_cc_change(deck.midi_channel(), control_number, encoding.encode(steps)),
+        Output::Parameter(parameter) => send_parameter_increment(deck.midi_channel(), parameter, steps),
+        // 1ステップを64として中央(8192)からずらす（±127ステップで全域）
+        Output::PitchBend => send_pitch_bend(deck.midi_channel(), (8192 + steps * 64).clamp(0, 16383) as u16),
+    };
+    if let Err(e) = result {
+        eprintln!("Failed to send MIDI {:?} ({} Deck): {:?}", output, deck.name(), e);
+    }
+}
+
+// キーリピートの状態を更新し、繰り返すタイミングになったかどうかを返す
+fn is_repeat_due(mapping: &'static CCMapping, pressed: bool, was_pressed: bool) -> bool {
+    let Some(auto_repeat) = mapping.auto_repeat else {
+        return false;
+    };
+    let mut repeat_state = REPEAT_STATE.lock().unwrap();
+    let key = mapping_key(mapping);
+    if !pressed {
+        repeat_state.remove(&key);
+        return false;
+    }
+    let now = Instant::now();
+    if !was_pressed {
+        repeat_state.insert(key, RepeatState {
+            next: now + Duration::from_millis(auto_repeat.delay_ms),
+            interval: Duration::from_millis(auto_repeat.interval_ms),
+        });
+        return false;
+    }
+    match repeat_state.get_mut(&key) {
+        Some(state) if now >= state.next => {
+            state.next = now + state.interval;
+            // 負の値やNaNではmul_f32がパニックするので、(0.0, 1.0]の範囲に収める（範囲外なら一定間隔）
+            let speedup = if auto_repeat.speedup > 0.0 { auto_repeat.speedup.min(1.0) } else { 1.0 };
+            state.interval = state.interval.mul_f32(speedup).max(Duration::from_millis(auto_repeat.min_interval_ms));
+            true
+        },
+        _ => false,
+    }
+}
+
+// 押している間、対象の値を最後に送信した値から増減させる
+fn process_ramp(mapping: &'static CCMapping, pressed: bool, rate: f32, acceleration: f32) {
+    let mut ramp_state = RAMP_STATE.lock().unwrap();
+    let key = mapping_key(mapping);
+    if !pressed {
+        ramp_state.remove(&key);
+        return;
+    }
+    let deck = mapping.deck;
+    // 対象のCCと、送信方法を決めるマッピング
+    let target = match mapping.cc_number {
+        Some(cc_number) => Some((cc_number, mapping)),
+        None if deck != DeckType::Common => {
+            let cc_number = get_current_cc(deck);
+            find_function_mapping(deck, cc_number)
+                .filter(|m| matches!(m.behavior, Behavior::CCAbsolute | Behavior::Axis { .. }))
+                .map(|m| (cc_number, m))
+        },
+        None => None,
+    };
+    let Some((control_number, target_mapping)) = target else {
+        return;
+    };
+
+    let now = Instant::now();
+    let state = ramp_state.entry(key).or_insert(RampState { pressed_at: now, last_update: now });
+    let dt = now.duration_since(state.last_update).as_secs_f32();
+    let held = now.duration_since(state.pressed_at).as_secs_f32();
+    state.last_update = now;
+
+    let speed = rate * (1.0 + acceleration * held);
+    let sent_key = (deck.midi_channel(), control_number);
+    let last_value = LAST_SENT_VALUE.lock().unwrap().get(&sent_key).copied()
+        .unwrap_or(target_mapping.default_value.unwrap_or(0.5));
+    let value = (last_value + speed * dt).clamp(0.0, 1.0);
+
+    // 送信する値が変わる時だけ送信し、それまでは端数を記録しておく
+    let quantize = |v: f32| target_mapping.output.quantize(target_mapping.curve.apply(v));
+    if quantize(value) != quantize(last_value) {
+        send_absolute_value(deck, control_number, target_mapping.output, target_mapping.curve, value);
+    } else {
+        LAST_SENT_VALUE.lock().unwrap().insert(sent_key, value);
+    }
+}
+
+// ノートの押下状態を更新し、必要に応じてノートオン/オフを送信する
+fn process_note(mapping: &'static CCMapping, deck: DeckType, note_number: u8, pressed: bool, was_pressed: bool, analog: f32) {
+    let (note_on, note_off) = NOTE_PRESS_STATE.lock().unwrap()
+        .entry(mapping_key(mapping))
+        .or_insert(PressState::Released(None))
+        .update(mapping.velocity, pressed, was_pressed, analog);
+
+    if let Some(velocity) = note_on {
+        if let Err(e) = send_note_on(deck.midi_channel(), note_number, velocity) {
+            eprintln!("Failed to send MIDI Note On ({} Deck): {:?}", deck.name(), e);
+        }
+    }
+    if note_off {
+        if let Err(e) = send_note_off(deck.midi_channel(), note_number) {
+            eprintln!("Failed to send MIDI Note Off ({} Deck): {:?}", deck.name(), e);
+        }
+    }
+}
+
+// マクロをイベントループとは別のスレッドで再生する
+// 同じマッピングのマクロが再生中の場合はそちらを中断する
+fn play_macro(mapping: &'static CCMapping, steps: &'static [MacroStep], on_release: bool) {
+    if steps.is_empty() {
+        return;
+    }
+    // 押した時と離した時のマクロは別々に数え、同じ方のマクロを再生し直した時だけ中断する
+    let key = (mapping_key(mapping), on_release);
+    let generation = {
+        let mut macro_runs = MACRO_RUNS.lock().unwrap();
+        let run = macro_runs.entry(key).or_default();
+        run.generation += 1;
+        // 中断した再生で鳴らしたままのノートは、新しい再生を始める前に止める
+        release_macro_notes(mapping, &mut run.sounding);
+        run.generation
+    };
 
-            match (is_pressed, note_state[stick_idx]) {
-                (true, false) => {
-                    // スティックが倒された
-                    note_state[stick_idx] = true;
-                    if let Err(e) = send_note_on(deck.midi_channel(), note_number, 127) {
-                        eprintln!("Failed to send MIDI Note On ({} Deck): {:?}",
-                            match deck {
-                                DeckType::Left => "Left",
-                                DeckType::Right => "Right",
-                                DeckType::Common => "Common",
-                            },
-                            e
-                        );
+    thread::spawn(move || {
+        for step in steps {
+            if let MacroStep::Wait(ms) = *step {
+                // 中断に素早く反応できるよう、短い間隔に区切って待つ
+                let until = Instant::now() + Duration::from_millis(ms);
+                while let Some(remaining) = until.checked_duration_since(Instant::now()) {
+                    if !*RUNNING.lock().unwrap() || MACRO_RUNS.lock().unwrap().get(&key).map(|run| run.generation) != Some(generation) {
+                        break;
                     }
+                    thread::sleep(remaining.min(Duration::from_millis(10)));
+                }
+            }
+
+            let running = *RUNNING.lock().unwrap();
+            // 送信と鳴らしているノートの記録は同じロックの中で行い、再生し直した時の後始末と入れ違わないようにする
+            let mut macro_runs = MACRO_RUNS.lock().unwrap();
+            let Some(run) = macro_runs.get_mut(&key).filter(|run| run.generation == generation) else {
+                return;
+            };
+            if !running {
+                // マッピングを停止した場合は鳴らしたままのノートを止める
+                release_macro_notes(mapping, &mut run.sounding);
+                return;
+            }
+            let result = match *step {
+                MacroStep::NoteOn { deck, note, velocity } => {
+                    run.sounding.push((deck.midi_channel(), note));
+                    send_note_on(deck.midi_channel(), note, velocity)
                 },
-                (false, true) => {
-                    // スティックが元に戻った
-                    note_state[stick_idx] = false;
-                    if let Err(e) = send_note_off(deck.midi_channel(), note_number) {
-                        eprintln!("Failed to send MIDI Note Off ({} Deck): {:?}",
-                            match deck {
-                                DeckType::Left => "Left",
-                                DeckType::Right => "Right",
-                                DeckType::Common => "Common",
-                            },
-                            e
-                        );
+                MacroStep::NoteOff { deck, note } => {
+                    // 押した時のマクロで鳴らしたノートを離した時のマクロで止める場合もある
+                    for on_release in [false, true] {
+                        if let Some(run) = macro_runs.get_mut(&(key.0, on_release)) {
+                            run.sounding.retain(|&sounding_note| sounding_note != (deck.midi_channel(), note));
+                        }
                     }
+                    send_note_off(deck.midi_channel(), note)
                 },
-                _ => (),
+                MacroStep::Note { deck, note } => send_note_on(deck.midi_channel(), note, 127)
+                    .and_then(|_| send_note_off(deck.midi_channel(), note)),
+                MacroStep::ControlChange { deck, cc, value } => send_cc_change(deck.midi_channel(), cc, value),
+                MacroStep::Wait(_) => Ok(()),
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to send MIDI in macro ({}): {:?}", mapping.description, e);
             }
         }
+        // 最後まで再生した場合は、鳴らしたままのノートもマクロの指定どおり残す
+    });
+}
 
-        // CC処理
-        let midi_value = match mapping.behavior {
-            Behavior::CCAbsolute => calculate_midi_cc_value_absolute(x, y, deadzone_cc),
-            Behavior::CCRelative => calculate_midi_cc_value_relative(x, y, deck, deadzone_cc, 360.0),
-            _ => None,
-        };
+// マクロで鳴らしたままのノートを止める
+fn release_macro_notes(mapping: &CCMapping, sounding: &mut Vec<(u8, u8)>) {
+    for (channel, note) in sounding.drain(..) {
+        if let Err(e) = send_note_off(channel, note) {
+            eprintln!("Failed to send MIDI in macro ({}): {:?}", mapping.description, e);
+        }
+    }
+}
 
-        if let Some(value) = midi_value {
-            if let Err(e) = send_cc_change(deck.midi_channel(), control_number, value) {
-                eprintln!("Failed to send MIDI CC ({} Deck): {:?}",
-                    match deck {
-                        DeckType::Left => "Left",
-                        DeckType::Right => "Right",
-                        DeckType::Common => "Common",
-                    },
-                    e
-                );
-            }
+// 機能を既定値に戻す（リセットジェスチャーが有効なマッピングのみ）
+fn reset_to_default(deck: DeckType, control_number: u8) {
+    if let Some(mapping) = find_function_mapping(deck, control_number) {
+        if let (true, Some(default_value)) = (mapping.reset_gesture, mapping.default_value) {
+            println!("{} deck {} reset to default", deck.name(), mapping.description);
+            send_absolute_value(deck, control_number, mapping.output, mapping.curve, default_value);
         }
     }
 }
@@ -369,11 +1437,12 @@ fn process_button(state: &ControllerState, last_left_cc: &mut u8, last_right_cc:
         } else {
             // 離された時
             if special_button_state.0 && state.buttons.select && is_quick_press(special_button_press_time.0) {
-                // スタートボタンの短押し処理
-                if let Err(e) = send_note_on(DeckType::Common.midi_channel(), 3, 127) {
+                // スタートボタンの短押し処理（右側で操作しているデッキにロード、デッキ2は3、デッキ4は5）
+                let note = 2 + DeckType::Right.deck_index() as u8;
+                if let Err(e) = send_note_on(DeckType::Common.midi_channel(), note, 127) {
                     eprintln!("Failed to send Note On for Start button: {:?}", e);
                 }
-                if let Err(e) = send_note_off(DeckType::Common.midi_channel(), 3) {
+                if let Err(e) = send_note_off(DeckType::Common.midi_channel(), note) {
                     eprintln!("Failed to send Note Off for Start button: {:?}", e);
                 }
             }
@@ -391,11 +1460,12 @@ fn process_button(state: &ControllerState, last_left_cc: &mut u8, last_right_cc:
         } else {
             // 離された時
             if special_button_state.1 && state.buttons.start && is_quick_press(special_button_press_time.1) {
-                // セレクトボタンの短押し処理
-                if let Err(e) = send_note_on(DeckType::Common.midi_channel(), 2, 127) {
+                // セレクトボタンの短押し処理（左側で操作しているデッキにロード、デッキ1は2、デッキ3は4）
+                let note = 2 + DeckType::Left.deck_index() as u8;
+                if let Err(e) = send_note_on(DeckType::Common.midi_channel(), note, 127) {
                     eprintln!("Failed to send Note On for Select button: {:?}", e);
                 }
-                if let Err(e) = send_note_off(DeckType::Common.midi_channel(), 2) {
+                if let Err(e) = send_note_off(DeckType::Common.midi_channel(), note) {
                     eprintln!("Failed to send Note Off for Select button: {:?}", e);
                 }
             }
@@ -404,71 +1474,308 @@ fn process_button(state: &ControllerState, last_left_cc: &mut u8, last_right_cc:
         }
     }
 
+    // リセットジェスチャー（スティック押し込みのダブルクリック）の処理
+    // 1回目のクリックでジョグに切り替わるので、その前に割り当てられていた機能をリセットして戻す
+    const RESET_BUTTONS: [(DeckType, ButtonGetter); 2] = [(DeckType::Left, |b| b.l_stick), (DeckType::Right, |b| b.r_stick)];
+    const DOUBLE_CLICK_MS: u128 = 400;
+    let mut reset_click = RESET_CLICK.lock().unwrap();
+    let mut reset_hold = RESET_HOLD.lock().unwrap();
+    let is_layer_a = !(state.buttons.start || state.buttons.select);
+    for (deck, button_getter) in RESET_BUTTONS {
+        let idx = deck.stick_index();
+        let current_pressed = button_getter(&state.buttons) && is_layer_a;
+        let was_pressed = last_button_state.as_ref().is_some_and(button_getter);
+        if !current_pressed {
+            reset_hold[idx] = None;
+        } else if !was_pressed {
+            match reset_click[idx] {
+                Some(click) if click.time.elapsed().as_millis() < DOUBLE_CLICK_MS => {
+                    reset_click[idx] = None;
+                    reset_hold[idx] = Some(click.previous_cc);
+                    reset_to_default(deck, click.previous_cc);
+                },
+                _ => reset_click[idx] = Some(LastClick { time: Instant::now(), previous_cc: get_current_cc(deck) }),
+            }
+        }
+    }
+
+    let buttons = without_crossfader_triggers(&active_mappings, &state.buttons);
+    let last_buttons = last_button_state.as_ref().map(|last_state| without_crossfader_triggers(&active_mappings, last_state));
+
     // 通常のボタンマッピング処理
-    for mapping in active_mappings.iter() {
-        let current_pressed = (mapping.button_getter)(&state.buttons);
-        let was_pressed = last_button_state.as_ref().map_or(false, |last_state| (mapping.button_getter)(last_state));
+    for &mapping in active_mappings.iter() {
+        let current_pressed = (mapping.button_getter)(&buttons);
+        let was_pressed = last_buttons.as_ref().map_or(false, |last_state| (mapping.button_getter)(last_state));
         
         match mapping.behavior {
             Behavior::Note => {
+                if let Some(note_number) = mapping.note_number {
+                    let analog = mapping.analog_getter.map_or(if current_pressed { 1.0 } else { 0.0 }, |getter| getter(state));
+                    process_note(mapping, mapping.deck, note_number, current_pressed, was_pressed, analog);
+                    if is_repeat_due(mapping, current_pressed, was_pressed) {
+                        // 押し続けている間はノートを打ち直す
+                        process_note(mapping, mapping.deck, note_number, false, true, 0.0);
+                        process_note(mapping, mapping.deck, note_number, true, false, analog);
+                    }
+                }
+            },
+            Behavior::Ramp { rate, acceleration } => {
+                process_ramp(mapping, current_pressed, rate, acceleration);
+            },
+            Behavior::Step { steps } => {
+                if let Some(cc_number) = mapping.cc_number {
+                    let is_due = is_repeat_due(mapping, current_pressed, was_pressed);
+                    if (current_pressed && !was_pressed) || is_due {
+                        send_relative_steps(mapping.deck, cc_number, mapping.output, mapping.encoding, steps);
+                    }
+                }
+            },
+            Behavior::RadialMenu { items } => {
+                const DEADZONE_RADIAL: f32 = 0.5;
+                let deck = mapping.deck;
+                let mut radial_menu = RADIAL_MENU.lock().unwrap();
+                let menu = &mut radial_menu[deck.stick_index()];
+                if current_pressed {
+                    let (x, y) = get_stick_position(state, deck);
+                    let hovered = calculate_radial_sector(x, y, DEADZONE_RADIAL, items.len()).or(menu.flatten());
+                    if hovered != menu.flatten() {
+                        // 選択中の機能が変わったことを振動で知らせる
+                        request_rumble(Rumble::TICK);
+                    }
+                    *menu = Some(hovered);
+                } else if let Some(selected) = menu.take() {
+                    // 離された瞬間に選択中の機能に切り替える
+                    if let Some(&cc_number) = selected.and_then(|i| items.get(i)) {
+                        select_function(deck, cc_number, last_left_cc, last_right_cc);
+                    }
+                }
+            },
+            Behavior::Cycle { items, direction, rumble } => {
+                if current_pressed && !was_pressed && !items.is_empty() {
+                    let deck = mapping.deck;
+                    // 現在の機能が選択肢にない場合は先頭（逆方向なら末尾）から始める
+                    let next = match items.iter().position(|&cc| cc == get_current_cc(deck)) {
+                        Some(index) => (index as i32 + direction as i32).rem_euclid(items.len() as i32) as usize,
+                        None if direction < 0 => items.len() - 1,
+                        None => 0,
+                    };
+                    select_function(deck, items[next], last_left_cc, last_right_cc);
+                    if rumble {
+                        request_rumble(Rumble { left: 0, right: 30000, duration: Duration::from_millis(80), count: next as u8 + 1 });
+                    }
+                }
+            },
+            Behavior::Toggle { on_velocity, off_velocity } => {
                 if let Some(note_number) = mapping.note_number {
                     if current_pressed && !was_pressed {
-                        // ボタンが押された瞬間
-                        if let Err(e) = send_note_on(mapping.deck.midi_channel(), note_number, 127) {
-                            eprintln!("Failed to send MIDI Note On ({} Deck): {:?}",
-                                match mapping.deck {
-                                    DeckType::Left => "Left",
-                                    DeckType::Right => "Right",
-                                    DeckType::Common => "Common",
-                                },
-                                e
-                            );
-                        }
-                    } else if !current_pressed && was_pressed {
-                        // ボタンが離された瞬間
-                        if let Err(e) = send_note_off(mapping.deck.midi_channel(), note_number) {
-                            eprintln!("Failed to send MIDI Note Off ({} Deck): {:?}",
-                                match mapping.deck {
-                                    DeckType::Left => "Left",
-                                    DeckType::Right => "Right",
-                                    DeckType::Common => "Common",
-                                },
-                                e
-                            );
+                        let mut toggle_state = TOGGLE_STATE.lock().unwrap();
+                        let is_on = toggle_state.entry(mapping_key(mapping)).or_insert(false);
+                        *is_on = !*is_on;
+                        let velocity = if *is_on { on_velocity } else { off_velocity };
+                        if let Err(e) = send_note_on(mapping.deck.midi_channel(), note_number, velocity) {
+                            eprintln!("Failed to send MIDI Note On ({} Deck): {:?}", mapping.deck.name(), e);
                         }
                     }
                 }
             },
-            _ => if current_pressed {
-                // CC系マッピングの処理
+            Behavior::Macro { on_press, on_release } => {
+                if current_pressed && !was_pressed {
+                    play_macro(mapping, on_press, false);
+                } else if !current_pressed && was_pressed {
+                    play_macro(mapping, on_release, true);
+                }
+            },
+            Behavior::DeckSelect => {
+                if current_pressed && !was_pressed {
+                    // スタート/セレクトを押したまま切り替えた場合は、離しても曲をロードしない
+                    *special_button_press_time = (None, None);
+                    match mapping.deck {
+                        DeckType::Left => toggle_deck_focus(DeckType::Left, last_left_cc),
+                        DeckType::Right => toggle_deck_focus(DeckType::Right, last_right_cc),
+                        DeckType::Common => (),
+                    }
+                }
+            },
+            Behavior::Crossfader { curve, cut } => {
+                // スティックの状態に関係なく毎回処理するので、ジョグでスクラッチ中も操作できる
                 if let Some(cc_number) = mapping.cc_number {
+                    let left = curve.apply(state.triggers.left, cut);
+                    let right = curve.apply(state.triggers.right, cut);
+                    let value = (0.5 + (right - left) / 2.0).clamp(0.0, 1.0);
+                    send_absolute_value_if_changed(mapping.deck, cc_number, mapping.output, mapping.curve, value);
+                }
+            },
+            _ => if let Some(cc_number) = mapping.cc_number {
+                // CC系マッピングの処理
+                if mapping.momentary && mapping.deck != DeckType::Common {
+                    let mut momentary_previous = MOMENTARY_PREVIOUS.lock().unwrap();
+                    let idx = mapping.deck.stick_index();
+                    if current_pressed && !was_pressed {
+                        momentary_previous[idx] = Some(get_current_cc(mapping.deck));
+                    } else if !current_pressed && was_pressed {
+                        // 離された瞬間に元の機能に戻す
+                        if let Some(previous_cc) = momentary_previous[idx].take() {
+                            select_function(mapping.deck, previous_cc, last_left_cc, last_right_cc);
+                        }
+                    }
+                }
+                if current_pressed {
                     match mapping.deck {
-                        DeckType::Left => {
+                        DeckType::Left if reset_hold[0].is_none() => {
                             update_cc_if_changed(DeckType::Left, cc_number, mapping.description, last_left_cc);
                         },
-                        DeckType::Right => {
+                        DeckType::Right if reset_hold[1].is_none() => {
                             update_cc_if_changed(DeckType::Right, cc_number, mapping.description, last_right_cc);
                         },
-                        DeckType::Common => (), // Commonの場合は何もしない
+                        _ => (), // Commonの場合とリセット中は何もしない
                     }
                 }
             }
         }
     }
 
+    // レイヤーを切り替えたらラジアルメニューは閉じる
+    if !is_layer_a {
+        *RADIAL_MENU.lock().unwrap() = [None; 2];
+    }
+
+    // リセットした機能に戻す
+    if let Some(cc) = reset_hold[0] {
+        update_cc_if_changed(DeckType::Left, cc, "Reset", last_left_cc);
+    }
+    if let Some(cc) = reset_hold[1] {
+        update_cc_if_changed(DeckType::Right, cc, "Reset", last_right_cc);
+    }
+
     // 現在の状態を保存
     *last_button_state = Some(state.buttons.clone());
 }
 
+// ボタンの左右を入れ替える（十字キーと右側のボタンは同じ機能の位置どうしを入れ替える）
+fn mirror_buttons(b: &ButtonState) -> ButtonState {
+    ButtonState {
+        south: b.down,
+        east: b.left,
+        west: b.right,
+        north: b.up,
+        up: b.north,
+        down: b.south,
+        left: b.east,
+        right: b.west,
+        l: b.r,
+        lt: b.rt,
+        r: b.l,
+        rt: b.lt,
+        l_stick: b.r_stick,
+        r_stick: b.l_stick,
+        start: b.start,
+        select: b.select,
+    }
+}
+
+// コントローラーの状態の左右を入れ替える
+fn mirror_state(state: ControllerState) -> ControllerState {
+    ControllerState {
+        sticks: StickState { left: state.sticks.right, right: state.sticks.left },
+        triggers: TriggerState { left: state.triggers.right, right: state.triggers.left },
+        buttons: mirror_buttons(&state.buttons),
+    }
+}
+
+// 左右を入れ替えた時に、入れ替え前の操作の状態を引き継がないようにする
+// 割り当てられている機能（CURRENT_CC）などはデッキ側の状態なので、そのまま入れ替えた側に引き継がれる
+fn swap_side_state(last_left_cc: u8, last_right_cc: u8) {
+    // 操作中のスティックのノートとピッチベンドは入れ替え前のデッキで止め、慣性も引き継がない
+    release_stick_note(DeckType::Left, last_left_cc);
+    release_stick_note(DeckType::Right, last_right_cc);
+    release_pitch_bend(DeckType::Left);
+    release_pitch_bend(DeckType::Right);
+    reset_takeover(DeckType::Left);
+    reset_takeover(DeckType::Right);
+    reset_jog_motion(DeckType::Left);
+    reset_jog_motion(DeckType::Right);
+
+    // スティックごとの途中の状態も、入れ替えた後のスティックには引き継がない
+    *FINE_ANCHOR.lock().unwrap() = [None; 2];
+    *FLICK_DIRECTION.lock().unwrap() = [None; 2];
+    *XY_PAD_ACTIVE.lock().unwrap() = [false; 2];
+    *LAST_DETENT.lock().unwrap() = [None; 2];
+
+    // スティックとボタンの最後の状態は物理的な位置の状態なので、入れ替えた後の位置に合わせる
+    LAST_STICK_POS.lock().unwrap().swap(0, 1);
+    LAST_STICK_TIME.lock().unwrap().swap(0, 1);
+    let mut last_button_state = LAST_BUTTON_STATE.lock().unwrap();
+    *last_button_state = last_button_state.as_ref().map(mirror_buttons);
+
+    // 押している途中のボタンによる操作は取り消す
+    *RADIAL_MENU.lock().unwrap() = [None; 2];
+    *MOMENTARY_PREVIOUS.lock().unwrap() = [None; 2];
+    *RESET_HOLD.lock().unwrap() = [None; 2];
+    *RESET_CLICK.lock().unwrap() = [None; 2];
+}
+
+// 左右のデッキを操作する側を入れ替える
+#[tauri::command]
+pub fn set_sides_swapped(swapped: bool) -> Result<(), String> {
+    // 状態の入れ替えはコントローラーの処理スレッドで行う
+    *SIDES_SWAPPED.lock().unwrap() = swapped;
+    Settings::set_swap_sides(swapped)
+}
+
+// レイヤーAのトリガーをクロスフェーダーとして使うかどうかを切り替える
+#[tauri::command]
+pub fn set_trigger_crossfader(enabled: bool) -> Result<(), String> {
+    *TRIGGER_CROSSFADER.lock().unwrap() = enabled;
+    Settings::set_trigger_crossfader(enabled)
+}
+
+#[derive(serde::Serialize)]
+pub struct DeckStatus {
+    deck_number: u8,
+    cc_number: u8,
+    function_name: String,
+}
+
+#[derive(serde::Serialize)]
+pub struct MappingStatus {
+    left: DeckStatus,
+    right: DeckStatus,
+}
+
+// 各スティックが操作しているデッキと、割り当てられている機能を取得する
+#[tauri::command]
+pub fn get_mapping_status() -> MappingStatus {
+    let deck_status = |deck: DeckType| {
+        let cc_number = get_current_cc(deck);
+        let function_name = find_function_mapping(deck, cc_number).map_or("", |m| m.function_name);
+        DeckStatus { deck_number: deck.deck_index() as u8 + 1, cc_number, function_name: function_name.to_string() }
+    };
+    MappingStatus {
+        left: deck_status(DeckType::Left),
+        right: deck_status(DeckType::Right),
+    }
+}
+
 fn handle_controller_events(rx: Receiver<ControllerState>) {
     const DEADZONE_CC: f32 = 0.75;   // CCおよびノートオン用のデッドゾーン
     const DEADZONE_OFF: f32 = 0.7;   // ノートオフ用のデッドゾーン
+    const DEADZONE_AXIS: f32 = 0.15; // 軸ごとの操作用のデッドゾーン
+    const DEADZONES: Deadzones = Deadzones { cc: DEADZONE_CC, note_on: DEADZONE_CC, note_off: DEADZONE_OFF, axis: DEADZONE_AXIS };
     let mut last_left_cc = get_current_cc(DeckType::Left);
     let mut last_right_cc = get_current_cc(DeckType::Right);
+    let mut sides_swapped = *SIDES_SWAPPED.lock().unwrap();
 
     while *RUNNING.lock().unwrap() {
         match rx.recv() {
             Ok(state) => {
+                // 左右の入れ替え
+                let swapped = *SIDES_SWAPPED.lock().unwrap();
+                if swapped != sides_swapped {
+                    swap_side_state(last_left_cc, last_right_cc);
+                    sides_swapped = swapped;
+                    println!("Sides swapped: {}", sides_swapped);
+                }
+                let state = if sides_swapped { mirror_state(state) } else { state };
                 let [left_x, left_y] = state.sticks.left;
                 let [right_x, right_y] = state.sticks.right;
                 
@@ -477,26 +1784,23 @@ fn handle_controller_events(rx: Receiver<ControllerState>) {
                 
                 // スティックの処理
                 if state.buttons.start || state.buttons.select {
-                    // レイヤーBではスティックの挙動はライブラリの曲選択で固定
-                    let midi_value = (
-                        calculate_midi_cc_value_relative(left_x, left_y, DeckType::Left, DEADZONE_CC, 12.0),
-                        calculate_midi_cc_value_relative(right_x, right_y, DeckType::Right, DEADZONE_CC, 12.0)
-                    );
-
-                    if let Some(value) = midi_value.0 {
-                        if let Err(e) = send_cc_change(DeckType::Common.midi_channel(), 0, value) {
-                            eprintln!("Failed to send MIDI CC (Common Deck): {:?}", e);
-                        }
-                    }
-                    if let Some(value) = midi_value.1 {
-                        if let Err(e) = send_cc_change(DeckType::Common.midi_channel(), 0, value) {
-                            eprintln!("Failed to send MIDI CC (Common Deck): {:?}", e);
-                        }
-                    }
+                    // レイヤーBではスティックごとに決められたマッピングに応じた挙動を行う
+                    process_stick(left_x, left_y, &STICK_MAPPINGS_B[0], DeckType::Left, &DEADZONES, None);
+                    process_stick(right_x, right_y, &STICK_MAPPINGS_B[1], DeckType::Right, &DEADZONES, None);
                 } else {
                     // レイヤーAでは現在設定されているCCに応じた挙動を行う
-                    process_stick(left_x, left_y, last_left_cc, DeckType::Left, DEADZONE_CC, DEADZONE_CC, DEADZONE_OFF);
-                    process_stick(right_x, right_y, last_right_cc, DeckType::Right, DEADZONE_CC, DEADZONE_CC, DEADZONE_OFF);
+                    let buttons = without_crossfader_triggers(&get_active_mappings(&state), &state.buttons);
+                    let left_modifier = get_active_modifier(DeckType::Left, &buttons);
+                    let right_modifier = get_active_modifier(DeckType::Right, &buttons);
+                    // ラジアルメニューを開いている間は、スティックを離しているものとして扱う
+                    let (left_x, left_y) = if is_radial_menu_open(DeckType::Left) { (0.0, 0.0) } else { (left_x, left_y) };
+                    let (right_x, right_y) = if is_radial_menu_open(DeckType::Right) { (0.0, 0.0) } else { (right_x, right_y) };
+                    if let Some(mapping) = find_function_mapping(DeckType::Left, last_left_cc) {
+                        process_stick(left_x, left_y, mapping, DeckType::Left, &DEADZONES, left_modifier);
+                    }
+                    if let Some(mapping) = find_function_mapping(DeckType::Right, last_right_cc) {
+                        process_stick(right_x, right_y, mapping, DeckType::Right, &DEADZONES, right_modifier);
+                    }
                 }
 
             }
@@ -504,3 +1808,251 @@ fn handle_controller_events(rx: Receiver<ControllerState>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takeover_picks_up_or_offsets_from_the_last_sent_value() {
+        let channel = DeckType::Left.midi_channel();
+        let set_last_value = |control_number: u8, value: f32| {
+            LAST_SENT_VALUE.lock().unwrap().insert((channel, control_number), value);
+        };
+        let takeover = |control_number: u8, mode: Takeover, value: Option<f32>| {
+            apply_takeover(DeckType::Left, DeckType::Left, control_number, mode, value)
+        };
+
+        // Jumpは角度をそのまま送る
+        reset_takeover(DeckType::Left);
+        set_last_value(0x70, 0.5);
+        assert_eq!(takeover(0x70, Takeover::Jump, Some(0.3)), Some(0.3));
+        assert_eq!(takeover(0x70, Takeover::Jump, None), None);
+
+        // Pickupは最後に送信した値を通過するまで送らない
+        assert_eq!(takeover(0x70, Takeover::Pickup, Some(0.2)), None);
+        assert_eq!(takeover(0x70, Takeover::Pickup, Some(0.4)), None);
+        assert_eq!(takeover(0x70, Takeover::Pickup, Some(0.6)), Some(0.6));
+        assert_eq!(takeover(0x70, Takeover::Pickup, Some(0.3)), Some(0.3));
+        assert_eq!(takeover(0x70, Takeover::Pickup, None), None);
+
+        // 近づいただけでも通過したとみなす
+        assert_eq!(takeover(0x70, Takeover::Pickup, Some(0.49)), Some(0.49));
+        assert_eq!(takeover(0x70, Takeover::Pickup, None), None);
+
+        // Offsetは最後に送信した値から角度の変化分だけ動かす
+        let offset = |value: f32| takeover(0x70, Takeover::Offset, Some(value)).unwrap();
+        assert!((offset(0.8) - 0.5).abs() < 1e-6);
+        assert!((offset(0.9) - 0.6).abs() < 1e-6);
+        assert_eq!(offset(0.1), 0.0);
+        assert_eq!(takeover(0x70, Takeover::Offset, None), None);
+
+        // 一度も送信していなければPickupでもそのまま送る
+        assert_eq!(takeover(0x71, Takeover::Pickup, Some(0.2)), Some(0.2));
+        assert_eq!(takeover(0x71, Takeover::Pickup, None), None);
+    }
+
+    #[test]
+    fn relative_encoding_encodes_direction_and_clamps() {
+        // (ステップ数, [TwosComplement, BinaryOffset, SignMagnitude, Mackie])
+        let cases: [(i32, [u8; 4]); 8] = [
+            (1, [1, 65, 1, 1]),
+            (-1, [127, 63, 65, 65]),
+            (15, [15, 79, 15, 15]),
+            (-15, [113, 49, 79, 79]),
+            (63, [63, 127, 63, 15]),
+            (64, [63, 127, 63, 15]),
+            (-64, [64, 0, 127, 79]),
+            (-100, [64, 0, 127, 79]),
+        ];
+        let encodings = [
+            RelativeEncoding::TwosComplement,
+            RelativeEncoding::BinaryOffset,
+            RelativeEncoding::SignMagnitude,
+            RelativeEncoding::Mackie,
+        ];
+        for (steps, expected) in cases {
+            for (encoding, expected) in encodings.iter().zip(expected) {
+                assert_eq!(encoding.encode(steps), expected, "{:?} {}", encoding, steps);
+            }
+        }
+    }
+
+    #[test]
+    fn acceleration_gain_follows_the_speed_range() {
+        let acceleration = Acceleration { min_speed: 1.0, max_speed: 3.0, min_gain: 1.0, max_gain: 5.0, exponent: 1.0 };
+        assert_eq!(acceleration.gain(0.0), 1.0);
+        assert_eq!(acceleration.gain(2.0), 3.0);
+        assert_eq!(acceleration.gain(10.0), 5.0);
+
+        let acceleration = Acceleration { exponent: 2.0, ..acceleration };
+        assert_eq!(acceleration.gain(2.0), 2.0);
+
+        // 範囲の幅が0の場合はmax_speedを境に切り替わる
+        let acceleration = Acceleration { min_speed: 2.0, max_speed: 2.0, ..acceleration };
+        assert_eq!(acceleration.gain(1.9), 1.0);
+        assert_eq!(acceleration.gain(2.0), 5.0);
+    }
+
+    #[test]
+    fn press_state_fixed_velocity() {
+        let mut state = PressState::Released(None);
+        assert_eq!(state.update(Velocity::Fixed(100), true, false, 1.0), (Some(100), false));
+        assert_eq!(state.update(Velocity::Fixed(100), true, true, 1.0), (None, false));
+        assert_eq!(state.update(Velocity::Fixed(100), false, true, 0.0), (None, true));
+        assert_eq!(state.update(Velocity::Fixed(100), false, false, 0.0), (None, false));
+    }
+
+    #[test]
+    fn press_state_depth_velocity_waits_for_peak() {
+        let mut state = PressState::Released(None);
+        assert_eq!(state.update(Velocity::Depth, true, false, 0.3), (None, false));
+        assert_eq!(state.update(Velocity::Depth, true, true, 0.6), (None, false));
+        // 増えなくなったら深さが確定する
+        assert_eq!(state.update(Velocity::Depth, true, true, 0.6), (Some(Velocity::from_ratio(0.6)), false));
+        assert_eq!(state.update(Velocity::Depth, false, true, 0.0), (None, true));
+
+        // 確定する前に離された場合はノートオンとノートオフを同時に送る
+        let mut state = PressState::Released(None);
+        assert_eq!(state.update(Velocity::Depth, true, false, 0.4), (None, false));
+        assert_eq!(state.update(Velocity::Depth, false, true, 0.0), (Some(Velocity::from_ratio(0.4)), true));
+    }
+
+    #[test]
+    fn press_state_speed_velocity_is_fastest_within_one_poll() {
+        let mut state = PressState::Released(None);
+        let velocity = Velocity::Speed { fastest_ms: 10, slowest_ms: 200 };
+        assert_eq!(state.update(velocity, true, false, 1.0), (Some(127), false));
+    }
+
+    #[test]
+    fn response_curves_keep_endpoints() {
+        let curves = [
+            ResponseCurve::Linear,
+            ResponseCurve::Exponential,
+            ResponseCurve::Logarithmic,
+            ResponseCurve::SCurve,
+            ResponseCurve::Custom(&[(0.0, 0.0), (1.0, 1.0)]),
+        ];
+        for curve in curves {
+            assert!(curve.apply(0.0).abs() < 1e-6, "{:?}", curve);
+            assert!((curve.apply(1.0) - 1.0).abs() < 1e-6, "{:?}", curve);
+            assert!((curve.apply(-1.0)).abs() < 1e-6, "{:?}", curve);
+            assert!((curve.apply(2.0) - 1.0).abs() < 1e-6, "{:?}", curve);
+        }
+        assert!((ResponseCurve::SCurve.apply(0.5) - 0.5).abs() < 1e-6);
+        assert!(ResponseCurve::Exponential.apply(0.5) < 0.5);
+        assert!(ResponseCurve::Logarithmic.apply(0.5) > 0.5);
+    }
+
+    #[test]
+    fn custom_curve_interpolates_and_holds_endpoints() {
+        let curve = ResponseCurve::Custom(&[(0.2, 0.1), (0.6, 0.5), (0.8, 0.9)]);
+        let cases = [
+            (0.0, 0.1),  // 最初の点より前は最初の値
+            (0.2, 0.1),
+            (0.4, 0.3),
+            (0.6, 0.5),
+            (0.7, 0.7),
+            (0.8, 0.9),
+            (1.0, 0.9),  // 最後の点より後は最後の値
+        ];
+        for (input, expected) in cases {
+            assert!((curve.apply(input) - expected).abs() < 1e-5, "{} -> {}", input, curve.apply(input));
+        }
+        assert_eq!(ResponseCurve::Custom(&[]).apply(0.3), 0.3);
+    }
+
+    #[test]
+    fn stick_axis_value_starts_at_the_deadzone_edge() {
+        let value = |x: f32, y: f32, axis: StickAxis| calculate_stick_value_axis(x, y, axis, 0.2);
+        assert_eq!(value(0.1, 1.0, StickAxis::X), None);
+        let cases = [
+            ((0.2, 0.0), 0.5),
+            ((0.6, 0.0), 0.75),
+            ((1.0, 0.0), 1.0),
+            ((-1.0, 0.0), 0.0),
+        ];
+        for ((x, y), expected) in cases {
+            assert!((value(x, y, StickAxis::X).unwrap() - expected).abs() < 1e-6, "({}, {})", x, y);
+        }
+        // Y軸は上に倒すと増加する
+        assert!((value(1.0, -0.6, StickAxis::Y).unwrap() - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn stick_detent_uses_hysteresis() {
+        // 12時方向は0.5なので4段階の2、右（3時方向）は3
+        assert_eq!(calculate_stick_detent(0.0, 1.0, 0.75, 4, None), Some(2));
+        assert_eq!(calculate_stick_detent(1.0, 0.0, 0.75, 4, None), Some(3));
+        // 前回の段階から少しはみ出しただけなら前回の段階のまま
+        assert_eq!(calculate_stick_detent(0.0, 1.0, 0.75, 4, Some(1)), Some(1));
+        assert_eq!(calculate_stick_detent(0.0, 1.0, 0.75, 4, Some(0)), Some(2));
+        // 右回りの端は最後の段階に収まる
+        assert_eq!(calculate_stick_detent(0.01, -1.0, 0.75, 4, None), Some(3));
+        assert_eq!(calculate_stick_detent(-0.01, -1.0, 0.75, 4, None), Some(0));
+        // デッドゾーン内
+        assert_eq!(calculate_stick_detent(0.1, 0.1, 0.75, 4, Some(2)), None);
+    }
+
+    #[test]
+    fn radial_sector_wraps_around_twelve_o_clock() {
+        let cases = [
+            ((0.0, 1.0), Some(0)),
+            ((-0.1, 1.0), Some(0)),  // 12時の少し左も先頭の項目
+            ((0.1, 1.0), Some(0)),
+            ((1.0, 0.0), Some(1)),
+            ((0.0, -1.0), Some(2)),
+            ((-1.0, 0.0), Some(3)),
+            ((0.1, 0.1), None),
+        ];
+        for ((x, y), expected) in cases {
+            assert_eq!(calculate_radial_sector(x, y, 0.5, 4), expected, "({}, {})", x, y);
+        }
+        assert_eq!(calculate_radial_sector(0.0, 1.0, 0.5, 0), None);
+    }
+
+    #[test]
+    fn inertia_coasts_after_a_fast_spin_and_stops_on_touch() {
+        let inertia = Inertia { min_speed: 20.0, friction: 0.0 };
+
+        // ゆっくり回して離しても慣性は付かない
+        reset_jog_motion(DeckType::Right);
+        assert_eq!(update_inertia(DeckType::Right, inertia, true, Some(0)), None);
+        assert_eq!(update_inertia(DeckType::Right, inertia, false, None), None);
+        assert!(!is_coasting(DeckType::Right, inertia));
+
+        // 素早く回して離すと同じ向きに回り続ける
+        reset_jog_motion(DeckType::Right);
+        assert_eq!(update_inertia(DeckType::Right, inertia, true, Some(-100)), None);
+        std::thread::sleep(Duration::from_millis(10));
+        let steps = update_inertia(DeckType::Right, inertia, false, None);
+        assert!(steps.is_some_and(|steps| steps < 0), "{:?}", steps);
+        assert!(is_coasting(DeckType::Right, inertia));
+
+        // 再び触れると止まる
+        assert_eq!(update_inertia(DeckType::Right, inertia, true, None), None);
+        assert!(!JOG_MOTION.lock().unwrap()[DeckType::Right.stick_index()].coasting);
+        reset_jog_motion(DeckType::Right);
+        assert!(!is_coasting(DeckType::Right, inertia));
+    }
+
+    #[test]
+    fn crossfader_curves_reach_the_end_at_their_travel() {
+        let cases = [
+            (CrossfaderCurve::Smooth, 0.5, 0.5),
+            (CrossfaderCurve::Smooth, 1.0, 1.0),
+            (CrossfaderCurve::SharpCut, 0.25, 0.5),
+            (CrossfaderCurve::SharpCut, 0.5, 1.0),
+            (CrossfaderCurve::Scratch, 0.05, 0.5),
+            (CrossfaderCurve::Scratch, 0.2, 1.0),
+        ];
+        for (curve, input, expected) in cases {
+            assert!((curve.apply(input, false) - expected).abs() < 1e-6, "{:?} {}", curve, input);
+        }
+        // トランスフォーマーは閾値で0か1に切り替わる
+        assert_eq!(CrossfaderCurve::Smooth.apply(0.09, true), 0.0);
+        assert_eq!(CrossfaderCurve::Smooth.apply(0.1, true), 1.0);
+        assert_eq!(CrossfaderCurve::Scratch.apply(0.0, false), 0.0);
+    }
+}
@@ -5,8 +5,8 @@ mod settings;
 
 use crate::xinput_handler::{start_xinput_thread, stop_xinput_thread};
 use crate::midi_handler::{open_midi_port, close_midi_port, get_midi_ports};
-use crate::input_mapper::{start_mapping, stop_mapping};
-use crate::settings::Settings;
+use crate::input_mapper::{start_mapping, stop_mapping, get_mapping_status, set_sides_swapped, set_trigger_crossfader};
+use crate::settings::{Settings, MidiChannels};
 
 #[tauri::command]
 fn start_system(midi_port: String) -> Result<String, String> {
@@ -35,6 +35,11 @@ fn get_settings() -> Settings {
     Settings::get_settings()
 }
 
+#[tauri::command]
+fn set_midi_channels(midi_channels: MidiChannels) -> Result<(), String> {
+    Settings::set_midi_channels(midi_channels)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -44,6 +49,10 @@ pub fn run() {
             stop_system,
             get_midi_ports,
             get_settings,
+            set_midi_channels,
+            get_mapping_status,
+            set_sides_swapped,
+            set_trigger_crossfader,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -5,6 +5,24 @@ lazy_static::lazy_static! {
     static ref MIDI_CONNECTION: Mutex<Option<MidiOutputConnection>> = Mutex::new(None);
 }
 
+// NRPN/RPNのパラメーター番号（14bit）
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum ParameterNumber {
+    Nrpn(u16),
+    Rpn(u16),
+}
+
+impl ParameterNumber {
+    // パラメーター番号を選択するCC番号（MSB, LSB）と番号を返す
+    fn select_cc(&self) -> (u8, u8, u16) {
+        match self {
+            ParameterNumber::Nrpn(number) => (99, 98, *number),
+            ParameterNumber::Rpn(number) => (101, 100, *number),
+        }
+    }
+}
+
 #[derive(serde::Serialize)]
 pub struct MidiDevice {
     id: String,
@@ -87,3 +105,63 @@ pub fn send_note_off(channel: u8, note: u8) -> Result<(), String> {
         Err("No MIDI connection available".to_string())
     }
 }
+
+pub fn send_pitch_bend(channel: u8, value: u16) -> Result<(), String> {
+    let mut midi_conn = MIDI_CONNECTION.lock().unwrap();
+    if let Some(conn) = midi_conn.as_mut() {
+        // MIDI Pitch Bend message: Status byte (0xE0 | channel), LSB, MSB (14bit, 8192が中央)
+        let message = [0xE0 | (channel & 0x0F), (value & 0x7F) as u8, ((value >> 7) & 0x7F) as u8];
+        conn.send(&message).map_err(|e| e.to_string())?;
+        Ok(())
+    } else {
+        Err("No MIDI connection available".to_string())
+    }
+}
+
+// 複数のMIDIメッセージを1回のロックでまとめて送信する
+// NRPN/RPNは途中に他のメッセージが割り込むと意味が変わってしまうため
+fn send_messages(messages: &[[u8; 3]]) -> Result<(), String> {
+    let mut midi_conn = MIDI_CONNECTION.lock().unwrap();
+    if let Some(conn) = midi_conn.as_mut() {
+        for message in messages {
+            conn.send(message).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    } else {
+        Err("No MIDI connection available".to_string())
+    }
+}
+
+pub fn send_parameter_value(channel: u8, parameter: ParameterNumber, value: u16) -> Result<(), String> {
+    let status = 0xB0 | (channel & 0x0F);
+    let (msb_cc, lsb_cc, number) = parameter.select_cc();
+    // パラメーター番号の選択 → Data Entry MSB (CC 6) → Data Entry LSB (CC 38) → RPN Null
+    send_messages(&[
+        [status, msb_cc, ((number >> 7) & 0x7F) as u8],
+        [status, lsb_cc, (number & 0x7F) as u8],
+        [status, 6, ((value >> 7) & 0x7F) as u8],
+        [status, 38, (value & 0x7F) as u8],
+        [status, 101, 127],
+        [status, 100, 127],
+    ])
+}
+
+// 相対値の送信にはData Increment (CC 96) / Data Decrement (CC 97) を使う
+// 規格上データバイトは無視されるので、1ステップにつき1メッセージ送信する（最大127ステップ）
+pub fn send_parameter_increment(channel: u8, parameter: ParameterNumber, delta: i32) -> Result<(), String> {
+    if delta == 0 {
+        return Ok(());
+    }
+    let status = 0xB0 | (channel & 0x0F);
+    let (msb_cc, lsb_cc, number) = parameter.select_cc();
+    let data_cc = if delta > 0 { 96 } else { 97 };
+    let mut messages = vec![
+        [status, msb_cc, ((number >> 7) & 0x7F) as u8],
+        [status, lsb_cc, (number & 0x7F) as u8],
+    ];
+    messages.extend(std::iter::repeat_n([status, data_cc, 0], delta.unsigned_abs().min(127) as usize));
+    // 最後にRPN Nullを送り、以降のData Entryで意図せずパラメーターが変わらないようにする
+    messages.push([status, 101, 127]);
+    messages.push([status, 100, 127]);
+    send_messages(&messages)
+}
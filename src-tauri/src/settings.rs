@@ -8,15 +8,40 @@ lazy_static::lazy_static! {
     static ref SETTINGS: Mutex<Settings> = Mutex::new(Settings::load().unwrap_or_default());
 }
 
+// 送信先のMIDIチャンネル（0から15）
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct MidiChannels {
+    pub decks: [u8; 4],  // デッキ1から4
+    pub common: u8,      // ライブラリ操作など、デッキに属さない操作
+}
+
+impl Default for MidiChannels {
+    fn default() -> Self {
+        MidiChannels {
+            decks: [0, 1, 2, 3],
+            common: 15,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings {
     default_midi_port: Option<String>,
+    #[serde(default)]
+    swap_sides: bool,  // 左右のデッキを操作する側を入れ替える
+    #[serde(default)]
+    midi_channels: MidiChannels,
+    #[serde(default)]
+    trigger_crossfader: bool,  // レイヤーAのトリガーをクロスフェーダーとして使う
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Settings {
             default_midi_port: None,
+            swap_sides: false,
+            midi_channels: MidiChannels::default(),
+            trigger_crossfader: false,
         }
     }
 }
@@ -70,4 +95,37 @@ impl Settings {
         settings.default_midi_port = port;
         settings.save()
     }
+
+    pub fn swap_sides(&self) -> bool {
+        self.swap_sides
+    }
+
+    pub fn set_swap_sides(swap_sides: bool) -> Result<(), String> {
+        let mut settings = SETTINGS.lock().unwrap();
+        settings.swap_sides = swap_sides;
+        settings.save()
+    }
+
+    pub fn trigger_crossfader(&self) -> bool {
+        self.trigger_crossfader
+    }
+
+    pub fn set_trigger_crossfader(trigger_crossfader: bool) -> Result<(), String> {
+        let mut settings = SETTINGS.lock().unwrap();
+        settings.trigger_crossfader = trigger_crossfader;
+        settings.save()
+    }
+
+    pub fn midi_channels(&self) -> MidiChannels {
+        self.midi_channels
+    }
+
+    pub fn set_midi_channels(midi_channels: MidiChannels) -> Result<(), String> {
+        if midi_channels.decks.iter().chain(std::iter::once(&midi_channels.common)).any(|&channel| channel > 15) {
+            return Err("MIDI channel must be between 1 and 16".to_string());
+        }
+        let mut settings = SETTINGS.lock().unwrap();
+        settings.midi_channels = midi_channels;
+        settings.save()
+    }
 }
@@ -2,6 +2,7 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use rusty_xinput::XInputHandle;
 use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct StickState {
@@ -9,6 +10,13 @@ pub struct StickState {
     pub right: [f32; 2],
 }
 
+// トリガーの押し込み量（0.0から1.0）
+#[derive(Debug)]
+pub struct TriggerState {
+    pub left: f32,
+    pub right: f32,
+}
+
 #[derive(Debug, Clone)]
 pub struct ButtonState {
     pub south: bool,
@@ -32,11 +40,40 @@ pub struct ButtonState {
 #[derive(Debug)]
 pub struct ControllerState {
     pub sticks: StickState,
+    pub triggers: TriggerState,
     pub buttons: ButtonState,
 }
 
+// 振動のリクエスト
+#[derive(Debug, Clone, Copy)]
+pub struct Rumble {
+    pub left: u16,   // 左モーター（低周波）の強さ
+    pub right: u16,  // 右モーター（高周波）の強さ
+    pub duration: Duration,  // 1回あたりの振動時間（振動の間隔も同じ長さ）
+    pub count: u8,   // 振動する回数
+}
+
+impl Rumble {
+    // 操作の区切りを知らせる短い振動
+    pub const TICK: Rumble = Rumble { left: 0, right: 20000, duration: Duration::from_millis(20), count: 1 };
+}
+
+// 再生中の振動
+struct RumblePlayback {
+    rumble: Rumble,
+    remaining: u8,  // 残りの振動回数
+    on: bool,       // 振動中かどうか
+    next: Instant,  // 次に振動の開始/停止を切り替える時刻
+}
+
 lazy_static::lazy_static! {
     static ref RUNNING: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+    static ref RUMBLE_REQUEST: Arc<Mutex<Option<Rumble>>> = Arc::new(Mutex::new(None));
+}
+
+// コントローラーを振動させる（振動に対応していないコントローラーでは何もしない）
+pub fn request_rumble(rumble: Rumble) {
+    *RUMBLE_REQUEST.lock().unwrap() = Some(rumble);
 }
 
 // XInputからの入力の受け取りを開始する
@@ -50,6 +87,8 @@ pub fn start_xinput_thread(state_sender: Sender<ControllerState>) -> Result<(),
         }
         *guard = true;
     }
+    // 前回の停止前に残っていた振動のリクエストは捨てる
+    *RUMBLE_REQUEST.lock().unwrap() = None;
 
     let handle = XInputHandle::load_default()
         .map_err(|e| {
@@ -68,6 +107,7 @@ pub fn start_xinput_thread(state_sender: Sender<ControllerState>) -> Result<(),
     thread::spawn(move || {
         let mut consecutive_errors = 0;
         const MAX_ERRORS: u32 = 5; // この回数だけエラーが続いたらコントローラーが切断されたとみなす
+        let mut rumble_playback: Option<RumblePlayback> = None;
 
         loop {
             if !*running.lock().unwrap() {
@@ -75,6 +115,34 @@ pub fn start_xinput_thread(state_sender: Sender<ControllerState>) -> Result<(),
             }
 
             thread::sleep(std::time::Duration::from_millis(16));
+
+            // 振動の開始と停止
+            if let Some(rumble) = RUMBLE_REQUEST.lock().unwrap().take() {
+                if rumble.count > 0 && handle.set_state(0, rumble.left, rumble.right).is_ok() {
+                    rumble_playback = Some(RumblePlayback {
+                        rumble,
+                        remaining: rumble.count - 1,
+                        on: true,
+                        next: Instant::now() + rumble.duration,
+                    });
+                }
+            }
+            let mut rumble_finished = false;
+            if let Some(playback) = rumble_playback.as_mut().filter(|p| Instant::now() >= p.next) {
+                if playback.on {
+                    let _ = handle.set_state(0, 0, 0);
+                    rumble_finished = playback.remaining == 0;
+                } else {
+                    let _ = handle.set_state(0, playback.rumble.left, playback.rumble.right);
+                    playback.remaining -= 1;
+                }
+                playback.on = !playback.on;
+                playback.next = Instant::now() + playback.rumble.duration;
+            }
+            if rumble_finished {
+                rumble_playback = None;
+            }
+
             match handle.get_state(0) {
                 Err(e) => {
                     consecutive_errors += 1;
@@ -94,6 +162,10 @@ pub fn start_xinput_thread(state_sender: Sender<ControllerState>) -> Result<(),
                             left: [left_stick.0, left_stick.1],
                             right: [right_stick.0, right_stick.1],
                         },
+                        triggers: TriggerState {
+                            left: state.left_trigger() as f32 / 255.0,
+                            right: state.right_trigger() as f32 / 255.0,
+                        },
                         buttons: ButtonState {
                             south: state.south_button(),      // A button
                             east: state.east_button(),        // B button
@@ -122,6 +194,8 @@ pub fn start_xinput_thread(state_sender: Sender<ControllerState>) -> Result<(),
             }
         }
 
+        // 振動中に停止・切断した場合もモーターを止める
+        let _ = handle.set_state(0, 0, 0);
         *running.lock().unwrap() = false;
     });
 
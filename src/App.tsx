@@ -7,8 +7,28 @@ interface MidiDevice {
   name: string;
 }
 
+interface DeckStatus {
+  deck_number: number;
+  cc_number: number;
+  function_name: string;
+}
+
+interface MappingStatus {
+  left: DeckStatus;
+  right: DeckStatus;
+}
+
+// 送信先のMIDIチャンネル（0から15）
+interface MidiChannels {
+  decks: [number, number, number, number];
+  common: number;
+}
+
 interface Settings {
   default_midi_port: string | null;
+  swap_sides: boolean;
+  midi_channels: MidiChannels;
+  trigger_crossfader: boolean;
 }
 
 function App() {
@@ -17,6 +37,10 @@ function App() {
   const [statusMessage, setStatusMessage] = useState<string>("");
   const [isRunning, setIsRunning] = useState(false);
   const [shouldAutoStart, setShouldAutoStart] = useState(false);
+  const [mappingStatus, setMappingStatus] = useState<MappingStatus | null>(null);
+  const [swapSides, setSwapSides] = useState(false);
+  const [triggerCrossfader, setTriggerCrossfader] = useState(false);
+  const [midiChannels, setMidiChannels] = useState<MidiChannels | null>(null);
 
   // 初期化
   useEffect(() => {
@@ -26,6 +50,9 @@ function App() {
         setMidiDevices(devices);
 
         const settings = await invoke<Settings>("get_settings");
+        setSwapSides(settings.swap_sides);
+        setTriggerCrossfader(settings.trigger_crossfader);
+        setMidiChannels(settings.midi_channels);
         if (settings.default_midi_port) {
           setSelectedMidiPort(settings.default_midi_port);
           
@@ -50,10 +77,70 @@ function App() {
     }
   }, [selectedMidiPort, shouldAutoStart, isRunning]);
 
+  // 動作中は各スティックに割り当てられている機能を定期的に取得する
+  useEffect(() => {
+    if (!isRunning) {
+      setMappingStatus(null);
+      return;
+    }
+    const timer = setInterval(async () => {
+      try {
+        setMappingStatus(await invoke<MappingStatus>("get_mapping_status"));
+      } catch (error) {
+        console.error("Failed to get mapping status:", error);
+      }
+    }, 200);
+    return () => clearInterval(timer);
+  }, [isRunning]);
+
   const handleMidiPortChange = (event: React.ChangeEvent<HTMLSelectElement>) => {
     setSelectedMidiPort(event.target.value);
   };
 
+  async function handleSwapSidesChange(event: React.ChangeEvent<HTMLInputElement>) {
+    const swapped = event.target.checked;
+    try {
+      await invoke("set_sides_swapped", { swapped });
+      setSwapSides(swapped);
+    } catch (error) {
+      console.error("Failed to swap sides:", error);
+      setStatusMessage(`Failed to swap sides: ${error}`);
+    }
+  }
+
+  async function handleTriggerCrossfaderChange(event: React.ChangeEvent<HTMLInputElement>) {
+    const enabled = event.target.checked;
+    try {
+      await invoke("set_trigger_crossfader", { enabled });
+      setTriggerCrossfader(enabled);
+    } catch (error) {
+      console.error("Failed to set trigger crossfader:", error);
+      setStatusMessage(`Failed to set trigger crossfader: ${error}`);
+    }
+  }
+
+  // チャンネルの変更は次回の開始時に反映される
+  async function updateMidiChannels(channels: MidiChannels) {
+    try {
+      await invoke("set_midi_channels", { midiChannels: channels });
+      setMidiChannels(channels);
+    } catch (error) {
+      console.error("Failed to set MIDI channels:", error);
+      setStatusMessage(`Failed to set MIDI channels: ${error}`);
+    }
+  }
+
+  const channelSelect = (label: string, value: number, onChange: (channel: number) => void) => (
+    <label key={label}>
+      {label}
+      <select value={value} onChange={(event) => onChange(Number(event.target.value))} disabled={isRunning}>
+        {Array.from({ length: 16 }, (_, channel) => (
+          <option key={channel} value={channel}>Ch {channel + 1}</option>
+        ))}
+      </select>
+    </label>
+  );
+
   async function startSystem() {
     if (!selectedMidiPort) {
       setStatusMessage("Please select a MIDI port first");
@@ -82,6 +169,10 @@ function App() {
     }
   }
 
+  // 左右を入れ替えている場合も、表示は実際に操作するスティックの側に合わせる
+  const deckStatusText = (side: string, status: DeckStatus) =>
+    `${side} (Deck ${status.deck_number}): ${status.function_name || `CC#${status.cc_number}`}`;
+
   return (
     <main className="container">
       <div className="midi-controls">
@@ -97,7 +188,34 @@ function App() {
             </option>
           ))}
         </select>
+        {midiChannels && (
+          <div className="midi-channels">
+            {midiChannels.decks.map((channel, index) =>
+              channelSelect(`Deck ${index + 1}`, channel, (value) => {
+                const decks = [...midiChannels.decks] as MidiChannels["decks"];
+                decks[index] = value;
+                updateMidiChannels({ ...midiChannels, decks });
+              })
+            )}
+            {channelSelect("Common", midiChannels.common, (value) => updateMidiChannels({ ...midiChannels, common: value }))}
+          </div>
+        )}
+        <label>
+          <input type="checkbox" checked={swapSides} onChange={handleSwapSidesChange} />
+          Swap left/right
+        </label>
+        <label>
+          <input type="checkbox" checked={triggerCrossfader} onChange={handleTriggerCrossfaderChange} />
+          Use triggers as crossfader
+        </label>
         <p>{statusMessage}</p>
+        {mappingStatus && (
+          <p>
+            {deckStatusText("L", swapSides ? mappingStatus.right : mappingStatus.left)}
+            {" / "}
+            {deckStatusText("R", swapSides ? mappingStatus.left : mappingStatus.right)}
+          </p>
+        )}
 
         <button 
           onClick={isRunning ? stopSystem : startSystem}